@@ -35,9 +35,12 @@ impl Module for MyModule {
 }
 
 impl Module for MyModule2 {
-    fn resolve(&mut self, src: PathBuf, dest: PathBuf) -> Resolved {
+    fn resolve_with(&mut self, ctx: &ModuleContext) -> Resolved {
         self.inc();
-        println!("src: {:?}, dest: {:?}", src, dest);
+        println!(
+            "{:?} src: {:?}, dest: {:?}, previous: {:?}",
+            ctx.kind, ctx.src, ctx.dest, ctx.previous
+        );
         Resolved::Continue
     }
 }
@@ -72,6 +75,10 @@ fn main() {
         .watch(r"r:\dev\a3\", |r| {
             r.add(&task1).add(&task2);
         })
-        .start()
+        .start(|msg| {
+            if let Msg::Outcome(outcome) = msg {
+                println!("{}", outcome.colored());
+            }
+        })
         .unwrap();
 }
@@ -0,0 +1,30 @@
+use watcher::*;
+
+fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let path = std::env::args()
+        .nth(1)
+        .filter(|a| !a.starts_with("--"))
+        .unwrap_or("examples/rules.toml".into());
+
+    let mut app = Watch::from_file(path)?;
+
+    // `--explain=some/file` shows how each task treats a newly created file
+    if let Some(file) =
+        std::env::args().find_map(|a| a.strip_prefix("--explain=").map(String::from))
+    {
+        let kind = EventKind::Create(notify::event::CreateKind::File);
+        for explanation in app.explain(file, kind) {
+            println!("{explanation}");
+        }
+        return Ok(());
+    }
+    app.stop_on_signal()?;
+    app.start(|msg| {
+        if let Msg::Outcome(outcome) = msg {
+            println!("{}", outcome.colored());
+        }
+    })?;
+    Ok(())
+}
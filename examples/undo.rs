@@ -0,0 +1,47 @@
+//! Reverts moves recorded with `--journal=path`.
+//!
+//! ```sh
+//! cargo run --example undo -- journal.toml --last=10
+//! cargo run --example undo -- journal.toml --since=2024-05-01T12:00:00 --until=2024-05-01T13:00:00
+//! ```
+//! Add `--dry-run` to only list what would be reverted.
+
+use watcher::*;
+
+use std::{collections::HashMap, time::SystemTime};
+
+fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut path = None;
+    let mut args = HashMap::new();
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            Some((key, value)) => _ = args.insert(key.to_owned(), value.to_owned()),
+            None if arg.starts_with("--") => _ = args.insert(arg, String::new()),
+            None => path = Some(arg),
+        }
+    }
+    let path = path.ok_or("usage: undo <journal> [--last=N | --since=TIME [--until=TIME]]")?;
+
+    let time = |key: &str| -> std::result::Result<Option<SystemTime>, Box<dyn std::error::Error>> {
+        let Some(value) = args.get(key) else {
+            return Ok(None);
+        };
+        let time = chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")?
+            .and_local_timezone(chrono::Local)
+            .single()
+            .ok_or("ambiguous local time")?;
+        Ok(Some(time.into()))
+    };
+    let which = match (time("--since")?, time("--until")?) {
+        (Some(from), Some(to)) => Undo::Between(from, to),
+        (Some(from), None) => Undo::Since(from),
+        (None, Some(to)) => Undo::Between(SystemTime::UNIX_EPOCH, to),
+        (None, None) => Undo::Last(args.get("--last").map_or(Ok(1), |n| n.parse())?),
+    };
+
+    let journal = Journal::new(path).dry_run(args.contains_key("--dry-run"));
+    for outcome in journal.undo(which)? {
+        println!("{}", outcome.colored());
+    }
+    Ok(())
+}
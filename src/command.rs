@@ -0,0 +1,246 @@
+use crossbeam_channel::{Receiver, RecvTimeoutError, unbounded};
+
+use std::{
+    ffi::OsString,
+    io::Read,
+    path::Path,
+    process::{Child, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Outcome, OutcomeKind, watcher::Origin};
+
+/// How long output is read after exit when there is no timeout.
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
+/// External program run on a matched path, see [`crate::Task::run_command`].
+///
+/// `{src}` and `{dest}` in arguments are replaced with the source and the
+/// destination. The child also gets `WATCHER_SRC`, `WATCHER_DEST`,
+/// `WATCHER_LABEL`, `WATCHER_EVENT` and `WATCHER_RULESET` in its environment.
+/// Exit code zero is reported as success, anything else as an error, with the
+/// captured output as message.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub(crate) program: String,
+    args: Vec<String>,
+    timeout: Option<Duration>,
+}
+
+impl Command {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Kill the program when it runs longer than `timeout`. Output is read for
+    /// what remains of it after the exit, a second without a timeout, so a
+    /// background child holding the pipes can not stall the queue.
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Program with substituted arguments, for display.
+    pub(crate) fn line(&self, src: &Path, dest: &Path) -> String {
+        let mut line = self.program.clone();
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(&substitute(arg, src, dest).to_string_lossy());
+        }
+        line
+    }
+
+    pub(crate) fn run(&self, src: &Path, dest: &Path, origin: &Origin) -> Outcome {
+        let failed = |message: String| Outcome::error(message).with_src(src.to_path_buf());
+
+        let started = Instant::now();
+        let child = std::process::Command::new(&self.program)
+            .args(self.args.iter().map(|arg| substitute(arg, src, dest)))
+            .env("WATCHER_SRC", src)
+            .env("WATCHER_DEST", dest)
+            .env("WATCHER_LABEL", origin.label.as_deref().unwrap_or_default())
+            .env("WATCHER_EVENT", format!("{:?}", origin.event))
+            .env("WATCHER_RULESET", &origin.ruleset)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) => return failed(format!("{}: {err}", self.program)),
+        };
+
+        // drain both pipes so a chatty child can not block on a full pipe
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+
+        let status = match wait(&mut child, self.timeout) {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                let timeout = self.timeout.unwrap_or_default();
+                return failed(format!("{} timed out after {timeout:?}", self.program));
+            }
+            Err(err) => return failed(format!("{}: {err}", self.program)),
+        };
+
+        // a background child may hold the pipes open long after the exit
+        let remaining = match self.timeout {
+            Some(timeout) => timeout.saturating_sub(started.elapsed()),
+            None => OUTPUT_GRACE,
+        };
+        let deadline = Instant::now() + remaining.max(Duration::from_millis(100));
+        let mut complete = true;
+        let output: Vec<String> = [stdout, stderr]
+            .iter()
+            .map(|rx| {
+                let (out, closed) = collect(rx, deadline);
+                complete &= closed;
+                String::from_utf8_lossy(&out).trim().to_owned()
+            })
+            .filter(|out| !out.is_empty())
+            .collect();
+        let mut output = output.join("\n");
+        if !complete {
+            output.push_str(match output.is_empty() {
+                true => "output cut short, pipes still open",
+                false => " (output cut short, pipes still open)",
+            });
+        }
+
+        match status.success() {
+            true => {
+                let outcome = Outcome::new(OutcomeKind::Success)
+                    .with_src(src.to_path_buf())
+                    .with_dest(dest.to_path_buf());
+                match output.is_empty() {
+                    true => outcome.with_message(format!("{} done", self.program)),
+                    false => outcome.with_message(output),
+                }
+            }
+            false => {
+                let code = status
+                    .code()
+                    .map_or("by signal".to_owned(), |code| format!("with {code}"));
+                match output.is_empty() {
+                    true => failed(format!("{} exited {code}", self.program)),
+                    false => failed(format!("{} exited {code}: {output}", self.program)),
+                }
+            }
+        }
+    }
+}
+
+/// Replaces `{src}` and `{dest}`, keeping paths which are not valid unicode intact.
+fn substitute(arg: &str, src: &Path, dest: &Path) -> OsString {
+    let mut out = OsString::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        out.push(&rest[..start]);
+        let tail = &rest[start..];
+        if let Some(tail) = tail.strip_prefix("{src}") {
+            out.push(src);
+            rest = tail;
+        } else if let Some(tail) = tail.strip_prefix("{dest}") {
+            out.push(dest);
+            rest = tail;
+        } else {
+            out.push("{");
+            rest = &tail[1..];
+        }
+    }
+    out.push(rest);
+    out
+}
+
+/// Reads `pipe` on its own thread, sending chunks as they arrive.
+fn drain(pipe: Option<impl Read + Send + 'static>) -> Receiver<Vec<u8>> {
+    let (tx, rx) = unbounded();
+    thread::spawn(move || {
+        let Some(mut pipe) = pipe else {
+            return;
+        };
+        let mut buf = [0; 8192];
+        while let Ok(n @ 1..) = pipe.read(&mut buf) {
+            if tx.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Output sent until the pipe closed or `deadline`, `false` when cut short.
+fn collect(rx: &Receiver<Vec<u8>>, deadline: Instant) -> (Vec<u8>, bool) {
+    let mut out = Vec::new();
+    loop {
+        match rx.recv_deadline(deadline) {
+            Ok(chunk) => out.extend(chunk),
+            Err(RecvTimeoutError::Disconnected) => return (out, true),
+            Err(RecvTimeoutError::Timeout) => return (out, false),
+        }
+    }
+}
+
+/// `None` when the timeout passed first.
+fn wait(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    use notify::EventKind;
+
+    #[test]
+    fn background_child_does_not_outlast_timeout() {
+        let command = Command::new("sh")
+            .args(["-c", "echo started; sleep 5 &"])
+            .timeout(Duration::from_millis(500));
+        let origin = Origin {
+            label: None,
+            ruleset: "/".into(),
+            event: EventKind::Any,
+        };
+        let started = Instant::now();
+        let outcome = command.run(Path::new("/a"), Path::new("/b"), &origin);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(outcome.kind, OutcomeKind::Success);
+        assert_eq!(
+            outcome.message.as_deref(),
+            Some("started (output cut short, pipes still open)")
+        );
+    }
+}
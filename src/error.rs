@@ -0,0 +1,137 @@
+use std::{fmt, io, path::PathBuf};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Misconfiguration found while building tasks and rulesets, or a failure of
+/// the underlying watcher.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Invalid regex given to [`crate::Task::set_path_match_pattern`].
+    Pattern(regex::Error),
+    /// Invalid glob given to [`crate::Task::include`] or [`crate::Task::exclude`].
+    Glob(globset::Error),
+    /// Destination directory could not be created.
+    Destination {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Invalid destination template, see [`crate::Task::set_destination`].
+    Template {
+        template: String,
+        reason: String,
+    },
+    /// Task added to a ruleset without any watch event.
+    MissingEvent {
+        label: Option<String>,
+    },
+    /// Journal could not be read or rewritten, see [`crate::Journal::undo`].
+    Journal {
+        path: PathBuf,
+        reason: String,
+    },
+    /// Empty program given to [`crate::Task::run_command`].
+    EmptyCommand,
+    /// Watched path ends with `*`, use [`crate::Ruleset::recursive_mode`] instead.
+    AsteriskSuffix(PathBuf),
+    PathNotFound(PathBuf),
+    Notify(notify::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pattern(err) => write!(f, "invalid pattern: {err}"),
+            Self::Glob(err) => write!(f, "invalid glob: {err}"),
+            Self::Destination { path, source } => {
+                write!(
+                    f,
+                    "could not create destination {}: {source}",
+                    path.display()
+                )
+            }
+            Self::Template { template, reason } => {
+                write!(f, "invalid destination template `{template}`: {reason}")
+            }
+            Self::MissingEvent { label } => write!(
+                f,
+                "required watch event for {} task missing",
+                label.as_deref().unwrap_or("unnamed")
+            ),
+            Self::Journal { path, reason } => {
+                write!(f, "journal {}: {reason}", path.display())
+            }
+            Self::EmptyCommand => f.write_str("command without program"),
+            Self::AsteriskSuffix(path) => write!(
+                f,
+                "asterisk (*) not allowed as suffix in path {}, use recursive mode instead",
+                path.display()
+            ),
+            Self::PathNotFound(path) => write!(f, "path not found: {}", path.display()),
+            Self::Notify(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Pattern(err) => Some(err),
+            Self::Glob(err) => Some(err),
+            Self::Destination { source, .. } => Some(source),
+            Self::Notify(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Same error kind and message, as the sources are not cloneable.
+impl Clone for Error {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Pattern(err) => Self::Pattern(err.clone()),
+            Self::Glob(err) => Self::Glob(err.clone()),
+            Self::Destination { path, source } => Self::Destination {
+                path: path.clone(),
+                source: io::Error::new(source.kind(), source.to_string()),
+            },
+            Self::Template { template, reason } => Self::Template {
+                template: template.clone(),
+                reason: reason.clone(),
+            },
+            Self::MissingEvent { label } => Self::MissingEvent {
+                label: label.clone(),
+            },
+            Self::Journal { path, reason } => Self::Journal {
+                path: path.clone(),
+                reason: reason.clone(),
+            },
+            Self::EmptyCommand => Self::EmptyCommand,
+            Self::AsteriskSuffix(path) => Self::AsteriskSuffix(path.clone()),
+            Self::PathNotFound(path) => Self::PathNotFound(path.clone()),
+            Self::Notify(err) => {
+                let mut copy = notify::Error::generic(&err.to_string());
+                copy.paths = err.paths.clone();
+                Self::Notify(copy)
+            }
+        }
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(value: notify::Error) -> Self {
+        Self::Notify(value)
+    }
+}
+
+impl From<globset::Error> for Error {
+    fn from(value: globset::Error) -> Self {
+        Self::Glob(value)
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(value: regex::Error) -> Self {
+        Self::Pattern(value)
+    }
+}
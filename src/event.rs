@@ -0,0 +1,144 @@
+use notify::{
+    EventKind,
+    event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+};
+
+use std::{fmt, ops::Not, path::Path};
+
+use crate::History;
+
+/// Event an [`EventFilter`] is asked about.
+#[derive(Debug, Clone, Copy)]
+pub struct Trigger<'t> {
+    pub kind: EventKind,
+    /// Path the tasks run on, the new name of a rename.
+    pub path: &'t Path,
+    /// Old name of a rename, when reported together with the new one.
+    pub from: Option<&'t Path>,
+    /// Watched path of the ruleset.
+    pub root: &'t Path,
+    /// Earlier events of `path`.
+    pub history: &'t History,
+}
+
+/// Owned check deciding which events a [`crate::Task`] runs on.
+///
+/// Built-ins are combined with [`EventFilter::any`], [`EventFilter::all`],
+/// [`EventFilter::or`], [`EventFilter::and`] and `!`. Closures taking the kind
+/// and the [`History`] convert into it directly.
+pub struct EventFilter<'a>(Box<dyn Fn(&Trigger) -> bool + Send + Sync + 'a>);
+
+impl<'a> EventFilter<'a> {
+    pub fn new(f: impl Fn(&Trigger) -> bool + Send + Sync + 'a) -> Self {
+        Self(Box::new(f))
+    }
+
+    pub fn matches(&self, trigger: &Trigger) -> bool {
+        (self.0)(trigger)
+    }
+
+    /// Matches when any of `filters` does.
+    pub fn any(filters: impl IntoIterator<Item = EventFilter<'a>>) -> Self {
+        let filters: Vec<_> = filters.into_iter().collect();
+        Self::new(move |t| filters.iter().any(|f| f.matches(t)))
+    }
+
+    /// Matches when all of `filters` do.
+    pub fn all(filters: impl IntoIterator<Item = EventFilter<'a>>) -> Self {
+        let filters: Vec<_> = filters.into_iter().collect();
+        Self::new(move |t| filters.iter().all(|f| f.matches(t)))
+    }
+
+    pub fn or(self, other: EventFilter<'a>) -> Self {
+        Self::any([self, other])
+    }
+
+    pub fn and(self, other: EventFilter<'a>) -> Self {
+        Self::all([self, other])
+    }
+
+    /// Every event.
+    pub fn always() -> Self {
+        Self::new(|_| true)
+    }
+
+    pub fn create() -> Self {
+        Self::new(|t| matches!(t.kind, EventKind::Create(_)))
+    }
+
+    /// Any modification, including metadata and renames.
+    pub fn modify() -> Self {
+        Self::new(|t| matches!(t.kind, EventKind::Modify(_)))
+    }
+
+    /// New name of a renamed path.
+    pub fn rename() -> Self {
+        Self::new(|t| {
+            matches!(
+                t.kind,
+                EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both))
+            )
+        })
+    }
+
+    /// Renamed into the directory of its new name, from outside of it.
+    pub fn rename_into() -> Self {
+        Self::new(|t| match t.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => true,
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                t.from.and_then(Path::parent) != t.path.parent()
+            }
+            _ => false,
+        })
+    }
+
+    /// The path is gone by the time tasks run, so only modules and commands
+    /// can act on it. Its kind is unknown too, so it passes only tasks using
+    /// [`crate::Task::watch_all`].
+    pub fn remove() -> Self {
+        Self::new(|t| matches!(t.kind, EventKind::Remove(_)))
+    }
+
+    /// File opened for writing was closed. Not reported on every platform.
+    pub fn close_write() -> Self {
+        Self::new(|t| {
+            matches!(
+                t.kind,
+                EventKind::Access(AccessKind::Close(AccessMode::Write))
+            )
+        })
+    }
+
+    /// Content modified after the path was created, within the kept [`History`].
+    pub fn create_then_modify() -> Self {
+        Self::new(|t| {
+            matches!(
+                t.kind,
+                EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any)
+            ) && t.history.any(|kind| matches!(kind, EventKind::Create(_)))
+        })
+    }
+}
+
+impl<'a, F> From<F> for EventFilter<'a>
+where
+    F: Fn(EventKind, &History) -> bool + Send + Sync + 'a,
+{
+    fn from(f: F) -> Self {
+        Self::new(move |t| f(t.kind, t.history))
+    }
+}
+
+impl Not for EventFilter<'_> {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::new(move |t| !self.matches(t))
+    }
+}
+
+impl fmt::Debug for EventFilter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventFilter")
+    }
+}
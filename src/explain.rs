@@ -0,0 +1,270 @@
+use notify::{EventKind, RecursiveMode};
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Config, ConflictPolicy, Dedupe, Evaluation, History, Ruleset, Trigger, Watch, WatchingKind,
+    watcher::{self, QueueTask},
+};
+
+/// Single decision made for a path, see [`Explanation`].
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// e.g. `event`, `pattern`, `module` or `conflict`
+    pub check: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// How one task of a ruleset treats a path, returned by [`Watch::explain`].
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub ruleset: PathBuf,
+    pub label: Option<String>,
+    /// In evaluation order, ends at the first failed check.
+    pub steps: Vec<Step>,
+}
+
+impl Explanation {
+    /// `true` when the task would act on the path.
+    pub fn matched(&self) -> bool {
+        self.steps.iter().all(|step| step.passed)
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ruleset.display())?;
+        if let Some(label) = &self.label {
+            write!(f, " [{label}]")?;
+        }
+        f.write_str(match self.matched() {
+            true => ": matched",
+            false => ": ignored",
+        })?;
+        for step in &self.steps {
+            let mark = if step.passed { '+' } else { '-' };
+            write!(f, "\n  {mark} {}", step.check)?;
+            if !step.detail.is_empty() {
+                write!(f, ": {}", step.detail)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Collects steps only when enabled, so [`crate::Task::parse`] pays nothing for it.
+pub(crate) struct Trace(Option<Vec<Step>>);
+
+impl Trace {
+    pub(crate) fn off() -> Self {
+        Self(None)
+    }
+
+    fn on() -> Self {
+        Self(Some(Vec::new()))
+    }
+
+    pub(crate) fn step(
+        &mut self,
+        check: &'static str,
+        passed: bool,
+        detail: impl FnOnce() -> String,
+    ) {
+        if let Some(steps) = &mut self.0 {
+            steps.push(Step {
+                check,
+                passed,
+                detail: detail(),
+            });
+        }
+    }
+
+    fn into_steps(self) -> Vec<Step> {
+        self.0.unwrap_or_default()
+    }
+}
+
+impl Watch<'_> {
+    /// Walks every task of every ruleset as if `kind` happened on `path`, without
+    /// touching anything on disk. Modules are asked to resolve as usual but with
+    /// [`crate::Config::dry_run`] set, so side effects are up to them. The
+    /// [`History`] seen by event checks is empty.
+    pub fn explain(&self, path: impl AsRef<Path>, kind: EventKind) -> Vec<Explanation> {
+        let path = std::path::absolute(path.as_ref()).unwrap_or(path.as_ref().to_path_buf());
+        let mut explanations = Vec::new();
+        for rule in &self.rules {
+            let tasks = rule.tasks.read().unwrap();
+            let mut claimed: Option<Option<String>> = None;
+            let mut done = false;
+            for inner in rule.order(&tasks).into_iter().map(|i| &tasks[i]) {
+                let task = inner.task.lock().unwrap();
+                let mut trace = Trace::on();
+                if done {
+                    trace.step("evaluation", false, || {
+                        format!("{:?}, an earlier task matched", rule.evaluation)
+                    });
+                } else if let Some(claims) =
+                    self.explain_task(&mut trace, rule, &inner.dest, &task, &path, kind)
+                {
+                    match &claimed {
+                        Some(by) => trace.step("evaluation", false, || {
+                            let by = by.as_deref().unwrap_or("unnamed");
+                            format!("already taken by task {by}")
+                        }),
+                        None if claims => claimed = Some(task.label.clone()),
+                        None => {}
+                    }
+                    done = rule.evaluation != Evaluation::All;
+                }
+                explanations.push(Explanation {
+                    ruleset: rule.watched_path.clone(),
+                    label: task.label.clone(),
+                    steps: trace.into_steps(),
+                });
+            }
+        }
+        explanations
+    }
+
+    /// Same order of checks as the watcher thread and the queue. Returns whether
+    /// the task claims the path when it acts on it at all.
+    fn explain_task(
+        &self,
+        trace: &mut Trace,
+        rule: &Ruleset,
+        dest: &Path,
+        task: &crate::Task,
+        path: &Path,
+        kind: EventKind,
+    ) -> Option<bool> {
+        let root = std::path::absolute(&rule.watched_path).unwrap_or(rule.watched_path.clone());
+        let covered = match rule.recursive_mode {
+            RecursiveMode::Recursive => path.starts_with(&root) && path != root,
+            RecursiveMode::NonRecursive => path.parent() == Some(&root),
+        };
+        trace.step("watched", covered, || format!("{:?}", rule.recursive_mode));
+        if !covered {
+            return None;
+        }
+
+        let fits = match task.watched_types {
+            WatchingKind::Dirs => path.is_dir(),
+            WatchingKind::Files => path.is_file(),
+            WatchingKind::All => true,
+        };
+        trace.step("kind", fits, || format!("{:?}", task.watched_types));
+        if !fits {
+            return None;
+        }
+
+        let history = History::default();
+        let trigger = Trigger {
+            kind,
+            path,
+            from: None,
+            root: &root,
+            history: &history,
+        };
+        let wanted = task
+            .event_check
+            .as_ref()
+            .is_some_and(|f| f.matches(&trigger));
+        trace.step("event", wanted, || format!("{kind:?}"));
+        if !wanted {
+            return None;
+        }
+
+        if let Some(period) = task.stable_period {
+            trace.step("stable", true, || {
+                format!("waits {period:?} for writes to settle")
+            });
+        }
+
+        // the module result is traced by parse itself
+        let config = Config {
+            dry_run: true,
+            ..self.config.clone()
+        };
+        let queue_task = task.parse_traced(&trigger, dest.to_owned(), &config, trace);
+        let claims = queue_task.claims();
+        match queue_task {
+            QueueTask::None => return None,
+            QueueTask::Move {
+                src,
+                dest,
+                action,
+                conflict,
+                dedupe,
+                ..
+            } => self.explain_conflict(trace, &src, &dest, action, conflict, dedupe),
+            _ => {}
+        }
+        Some(claims)
+    }
+
+    fn explain_conflict(
+        &self,
+        trace: &mut Trace,
+        src: &Path,
+        dest: &Path,
+        action: crate::Action,
+        conflict: ConflictPolicy,
+        dedupe: Option<Dedupe>,
+    ) {
+        if let Some(mode) = dedupe
+            && src.is_file()
+            && let Ok(Some(existing)) = watcher::find_duplicate(src, dest)
+        {
+            let moves = action == crate::Action::Move;
+            trace.step("dedupe", moves, || match moves {
+                true => format!("{mode:?}, duplicate of {}", existing.display()),
+                false => format!("skipped, duplicate of {}", existing.display()),
+            });
+            return;
+        }
+
+        if !matches!(dest.try_exists(), Ok(true)) {
+            trace.step("conflict", true, || "destination free".into());
+            return;
+        }
+        let dump = || {
+            self.config
+                .dump_folder
+                .join(src.file_name().unwrap_or_default())
+        };
+        let replaces = matches!(
+            conflict,
+            ConflictPolicy::Overwrite | ConflictPolicy::KeepNewer | ConflictPolicy::KeepLarger
+        );
+        if replaces && let Some(reason) = watcher::kind_mismatch(src, dest) {
+            trace.step("conflict", false, || format!("skipped, {reason}"));
+            return;
+        }
+        let (passed, detail) = match conflict {
+            ConflictPolicy::Skip => (false, "skipped, destination exists".into()),
+            ConflictPolicy::Overwrite => (true, "overwrites destination".into()),
+            ConflictPolicy::KeepNewer => match watcher::is_newer(src, dest) {
+                true => (true, "overwrites older destination".into()),
+                false => (false, "skipped, destination is newer".into()),
+            },
+            ConflictPolicy::KeepLarger => match watcher::is_larger(src, dest) {
+                true => (true, "overwrites smaller destination".into()),
+                false => (false, "skipped, destination is larger".into()),
+            },
+            ConflictPolicy::KeepBoth => (
+                true,
+                format!("kept both as {}", watcher::numbered(dest).display()),
+            ),
+            ConflictPolicy::DumpFolder => (true, format!("diverted to {}", dump().display())),
+            ConflictPolicy::Ask => (
+                true,
+                format!("module decides, else diverted to {}", dump().display()),
+            ),
+        };
+        trace.step("conflict", passed, || detail);
+    }
+}
@@ -0,0 +1,105 @@
+use notify::EventKind;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Earlier events of the path an event check is asked about, newest first.
+/// See [`crate::Ruleset::event_history`].
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    events: Vec<(EventKind, Instant)>,
+}
+
+impl History {
+    /// Kind of the event right before the current one.
+    pub fn previous(&self) -> Option<EventKind> {
+        self.events.first().map(|(kind, _)| *kind)
+    }
+
+    /// Kinds with their age, newest first.
+    pub fn iter(&self) -> impl Iterator<Item = (EventKind, Duration)> + '_ {
+        self.events.iter().map(|(kind, at)| (*kind, at.elapsed()))
+    }
+
+    /// `true` when any earlier event satisfies `f`.
+    pub fn any(&self, f: impl Fn(&EventKind) -> bool) -> bool {
+        self.events.iter().any(|(kind, _)| f(kind))
+    }
+
+    /// `true` when an event satisfying `f` happened within `window`.
+    pub fn within(&self, window: Duration, f: impl Fn(&EventKind) -> bool) -> bool {
+        self.events
+            .iter()
+            .take_while(|(_, at)| at.elapsed() <= window)
+            .any(|(kind, _)| f(kind))
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Recent events of a ruleset keyed by full path. Entries older than `ttl` are
+/// dropped, at most `size` are kept per path.
+#[derive(Debug)]
+pub(crate) struct EventHistory {
+    pub(crate) size: usize,
+    pub(crate) ttl: Duration,
+    paths: HashMap<PathBuf, VecDeque<(EventKind, Instant)>>,
+    pruned: Instant,
+}
+
+impl Default for EventHistory {
+    fn default() -> Self {
+        Self {
+            size: 8,
+            ttl: Duration::from_secs(60),
+            paths: HashMap::new(),
+            pruned: Instant::now(),
+        }
+    }
+}
+
+impl EventHistory {
+    /// Snapshot for `path`, not including the event being dispatched.
+    pub(crate) fn get(&self, path: &Path) -> History {
+        let events = self
+            .paths
+            .get(path)
+            .into_iter()
+            .flatten()
+            .rev()
+            .take_while(|(_, at)| at.elapsed() <= self.ttl)
+            .copied()
+            .collect();
+        History { events }
+    }
+
+    pub(crate) fn push(&mut self, path: &Path, kind: EventKind) {
+        if self.size == 0 {
+            return;
+        }
+        // sweep other paths once per ttl, so bursts do not pay for it
+        if self.pruned.elapsed() > self.ttl {
+            let ttl = self.ttl;
+            self.paths.retain(|_, events| {
+                events.retain(|(_, at)| at.elapsed() <= ttl);
+                !events.is_empty()
+            });
+            self.pruned = Instant::now();
+        }
+
+        let events = self.paths.entry(path.to_path_buf()).or_default();
+        if events.len() >= self.size {
+            events.pop_front();
+        }
+        events.push_back((kind, Instant::now()));
+    }
+}
@@ -0,0 +1,509 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Error, Outcome, OutcomeKind, watcher::Origin};
+
+/// Persistent record of performed operations, see [`crate::Config::journal`].
+///
+/// Every entry is an `[[entry]]` table appended to a TOML file, so the journal
+/// stays readable and can be edited by hand.
+pub struct Journal {
+    path: PathBuf,
+    dry_run: bool,
+}
+
+/// Which entries [`Journal::undo`] reverts.
+#[derive(Debug, Clone, Copy)]
+pub enum Undo {
+    /// The last `n` entries.
+    Last(usize),
+    /// Entries recorded at or after the given time.
+    Since(SystemTime),
+    /// Entries recorded within `from..=to`.
+    Between(SystemTime, SystemTime),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JournalFile {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+/// Completed operation.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    /// RFC 3339
+    time: String,
+    op: Op,
+    #[serde(with = "raw_path")]
+    src: PathBuf,
+    #[serde(with = "raw_path")]
+    dest: PathBuf,
+    label: Option<String>,
+    #[serde(default, with = "raw_path::option")]
+    ruleset: Option<PathBuf>,
+    /// Moved to the dump folder instead of its destination.
+    #[serde(default)]
+    pub(crate) diverted: bool,
+    /// Timestamp added to the name in the dump folder.
+    #[serde(default)]
+    pub(crate) timestamped: bool,
+    /// Previous destination was overwritten, its content is lost.
+    #[serde(default)]
+    pub(crate) replaced: bool,
+    /// Size and mtime of `dest` right after the operation.
+    size: u64,
+    mtime: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Op {
+    Move,
+    Copy,
+    Hardlink,
+    Symlink,
+    Trash,
+    /// Source removed for good, `dest` is the source too. Not revertible.
+    Delete,
+    /// Source removed as a duplicate of `dest`, reverted by copying it back.
+    DedupeDrop,
+    /// Source removed and `dest` hard linked to its duplicate, reverted by
+    /// copying `dest` back and removing the link.
+    DedupeLink,
+}
+
+/// Paths which are not valid unicode are stored as `{ raw = [...], lossy = "..." }`,
+/// `raw` being bytes on unix and UTF-16 code units on windows.
+mod raw_path {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use std::{
+        ffi::OsString,
+        path::{Path, PathBuf},
+    };
+
+    #[cfg(unix)]
+    type Unit = u8;
+    #[cfg(windows)]
+    type Unit = u16;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Text(String),
+        Raw { raw: Vec<Unit>, lossy: String },
+    }
+
+    impl From<&Path> for Stored {
+        fn from(path: &Path) -> Self {
+            match path.to_str() {
+                Some(text) => Self::Text(text.to_owned()),
+                None => Self::Raw {
+                    raw: units(path),
+                    lossy: path.to_string_lossy().into_owned(),
+                },
+            }
+        }
+    }
+
+    impl From<Stored> for PathBuf {
+        fn from(stored: Stored) -> Self {
+            match stored {
+                Stored::Text(text) => text.into(),
+                Stored::Raw { raw, .. } => from_units(raw).into(),
+            }
+        }
+    }
+
+    pub(super) fn serialize<S: Serializer>(path: &Path, s: S) -> Result<S::Ok, S::Error> {
+        Stored::from(path).serialize(s)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<PathBuf, D::Error> {
+        Stored::deserialize(d).map(Into::into)
+    }
+
+    pub(super) mod option {
+        use super::*;
+
+        pub(in super::super) fn serialize<S: Serializer>(
+            path: &Option<PathBuf>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            path.as_deref().map(Stored::from).serialize(s)
+        }
+
+        pub(in super::super) fn deserialize<'de, D: Deserializer<'de>>(
+            d: D,
+        ) -> Result<Option<PathBuf>, D::Error> {
+            Option::<Stored>::deserialize(d).map(|stored| stored.map(Into::into))
+        }
+    }
+
+    #[cfg(unix)]
+    fn units(path: &Path) -> Vec<Unit> {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(unix)]
+    fn from_units(raw: Vec<Unit>) -> OsString {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(raw)
+    }
+
+    #[cfg(windows)]
+    fn units(path: &Path) -> Vec<Unit> {
+        use std::os::windows::ffi::OsStrExt;
+        path.as_os_str().encode_wide().collect()
+    }
+
+    #[cfg(windows)]
+    fn from_units(raw: Vec<Unit>) -> OsString {
+        use std::os::windows::ffi::OsStringExt;
+        OsString::from_wide(&raw)
+    }
+}
+
+impl Entry {
+    pub(crate) fn new(op: Op, src: &Path, dest: &Path, origin: &Origin) -> io::Result<Self> {
+        let (size, mtime) = match op {
+            Op::Delete => (0, 0),
+            _ => fingerprint(dest)?,
+        };
+        Ok(Self {
+            time: DateTime::<Local>::from(SystemTime::now()).to_rfc3339(),
+            op,
+            src: src.to_path_buf(),
+            dest: dest.to_path_buf(),
+            label: origin.label.clone(),
+            ruleset: Some(origin.ruleset.clone()),
+            diverted: false,
+            timestamped: false,
+            replaced: false,
+            size,
+            mtime,
+        })
+    }
+
+    fn time(&self) -> Option<SystemTime> {
+        DateTime::parse_from_rfc3339(&self.time)
+            .ok()
+            .map(Into::into)
+    }
+}
+
+/// Appends a single entry.
+pub(crate) fn append(path: &Path, entry: Entry) -> io::Result<()> {
+    let table = JournalFile { entry: vec![entry] };
+    let text = toml::to_string(&table).map_err(io::Error::other)?;
+    let mut file = fs::File::options().create(true).append(true).open(path)?;
+    writeln!(file, "{text}")
+}
+
+impl Journal {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            dry_run: false,
+        }
+    }
+
+    /// Only report what [`Journal::undo`] would revert.
+    pub const fn dry_run(mut self, yes: bool) -> Self {
+        self.dry_run = yes;
+        self
+    }
+
+    /// Reverts matching entries, newest first. Entries whose destination was
+    /// changed or removed since are refused and kept in the journal, reverted
+    /// ones are removed from it.
+    ///
+    /// Should not run while a [`crate::Watch`] writes to the same journal.
+    pub fn undo(&self, which: Undo) -> crate::Result<Vec<Outcome>> {
+        let mut file = self.read()?;
+        let count = file.entry.len();
+        let selected: Vec<usize> = match which {
+            Undo::Last(n) => (count.saturating_sub(n)..count).collect(),
+            Undo::Since(from) => self.within(&file, from, None),
+            Undo::Between(from, to) => self.within(&file, from, Some(to)),
+        };
+
+        let mut outcomes = Vec::with_capacity(selected.len());
+        let mut reverted = Vec::new();
+        for i in selected.into_iter().rev() {
+            let outcome = self.revert(&file.entry[i]);
+            if outcome.kind == OutcomeKind::Reverted && !self.dry_run {
+                reverted.push(i);
+            }
+            outcomes.push(outcome);
+        }
+
+        if !reverted.is_empty() {
+            let mut i = 0;
+            file.entry.retain(|_| {
+                i += 1;
+                !reverted.contains(&(i - 1))
+            });
+            let text = toml::to_string(&file).map_err(|e| self.error(e))?;
+            fs::write(&self.path, text).map_err(|e| self.error(e))?;
+        }
+        Ok(outcomes)
+    }
+
+    fn read(&self) -> crate::Result<JournalFile> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| self.error(e)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(JournalFile::default()),
+            Err(err) => Err(self.error(err)),
+        }
+    }
+
+    fn within(&self, file: &JournalFile, from: SystemTime, to: Option<SystemTime>) -> Vec<usize> {
+        file.entry
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                entry
+                    .time()
+                    .is_some_and(|time| time >= from && to.is_none_or(|to| time <= to))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn revert(&self, entry: &Entry) -> Outcome {
+        let refused = |reason: &str| {
+            Outcome::new(OutcomeKind::Skipped)
+                .with_src(entry.dest.clone())
+                .with_dest(entry.src.clone())
+                .with_message(reason)
+        };
+
+        if entry.op == Op::Delete {
+            return refused("not revertible, deleted");
+        }
+        match fingerprint(&entry.dest) {
+            Ok(found) if found == (entry.size, entry.mtime) => {}
+            Ok(_) => return refused("changed since"),
+            Err(_) => return refused("missing"),
+        }
+        let moves_back = matches!(
+            entry.op,
+            Op::Move | Op::Trash | Op::DedupeDrop | Op::DedupeLink
+        );
+        if moves_back && entry.src.symlink_metadata().is_ok() {
+            return refused("source path taken");
+        }
+
+        let mut outcome = Outcome::new(OutcomeKind::Reverted)
+            .with_src(entry.dest.clone())
+            .with_dest(entry.src.clone());
+        outcome.label = entry.label.clone();
+        outcome.ruleset = entry.ruleset.clone();
+        if entry.replaced {
+            outcome
+                .message
+                .replace("overwritten content is lost".into());
+        }
+        if self.dry_run {
+            outcome.dry_run = true;
+            return outcome;
+        }
+
+        let restore = || {
+            entry
+                .src
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::copy(&entry.dest, &entry.src))
+        };
+        let done = match entry.op {
+            Op::Move | Op::Trash => entry
+                .src
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| crate::watcher::move_path(&entry.dest, &entry.src))
+                .map(|_| {
+                    if entry.op == Op::Trash {
+                        let _ = fs::remove_file(trash_info(&entry.dest));
+                    }
+                }),
+            Op::Copy if entry.dest.is_dir() => fs::remove_dir_all(&entry.dest),
+            Op::Copy | Op::Hardlink | Op::Symlink => fs::remove_file(&entry.dest),
+            Op::DedupeDrop => restore().map(|_| ()),
+            Op::DedupeLink => restore().and_then(|_| fs::remove_file(&entry.dest)),
+            Op::Delete => unreachable!("refused above"),
+        };
+        match done {
+            Ok(_) => outcome,
+            Err(err) => Outcome::error(err.to_string()).with_src(entry.dest.clone()),
+        }
+    }
+
+    fn error(&self, reason: impl ToString) -> Error {
+        Error::Journal {
+            path: self.path.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Size and mtime in nanoseconds, links are not followed.
+fn fingerprint(path: &Path) -> io::Result<(u64, i64)> {
+    let meta = path.symlink_metadata()?;
+    let mtime = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as i64);
+    Ok((meta.len(), mtime))
+}
+
+/// `Trash/files/name` is described by `Trash/info/name.trashinfo`.
+fn trash_info(trashed: &Path) -> PathBuf {
+    let mut name = trashed.file_name().unwrap_or_default().to_os_string();
+    name.push(".trashinfo");
+    let trash = trashed.parent().and_then(Path::parent).unwrap_or(trashed);
+    trash.join("info").join(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use notify::EventKind;
+    use std::time::Duration;
+
+    fn origin(dir: &Path) -> Origin {
+        Origin {
+            label: Some("test".into()),
+            ruleset: dir.to_path_buf(),
+            event: EventKind::Any,
+        }
+    }
+
+    /// Moves `in/name` to `out/name` and journals it as done `ago`.
+    fn moved(dir: &Path, journal: &Path, name: &str, ago: Duration) -> (PathBuf, PathBuf) {
+        let (src, dest) = (dir.join("in").join(name), dir.join("out").join(name));
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::write(&dest, name).unwrap();
+        let mut entry = Entry::new(Op::Move, &src, &dest, &origin(dir)).unwrap();
+        entry.time = DateTime::<Local>::from(SystemTime::now() - ago).to_rfc3339();
+        append(journal, entry).unwrap();
+        (src, dest)
+    }
+
+    fn entries(journal: &Path) -> usize {
+        Journal::new(journal).read().unwrap().entry.len()
+    }
+
+    #[test]
+    fn undo_last_reverts_newest() {
+        let dir = crate::scratch("journal-last");
+        let journal = dir.join("journal.toml");
+        let (src_a, dest_a) = moved(&dir, &journal, "a.txt", Duration::from_secs(60));
+        let (src_b, dest_b) = moved(&dir, &journal, "b.txt", Duration::ZERO);
+
+        let outcomes = Journal::new(&journal).undo(Undo::Last(1)).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].kind, OutcomeKind::Reverted);
+        assert_eq!(fs::read_to_string(&src_b).unwrap(), "b.txt");
+        assert!(!dest_b.exists());
+        assert!(dest_a.exists() && !src_a.exists());
+        assert_eq!(entries(&journal), 1);
+    }
+
+    #[test]
+    fn undo_since_reverts_later_entries() {
+        let dir = crate::scratch("journal-since");
+        let journal = dir.join("journal.toml");
+        let (src_a, _) = moved(&dir, &journal, "a.txt", Duration::from_secs(3600));
+        let (src_b, _) = moved(&dir, &journal, "b.txt", Duration::from_secs(60));
+
+        let since = SystemTime::now() - Duration::from_secs(600);
+        let outcomes = Journal::new(&journal).undo(Undo::Since(since)).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(src_b.exists());
+        assert!(!src_a.exists());
+        assert_eq!(entries(&journal), 1);
+    }
+
+    #[test]
+    fn undo_refuses_changed_destination() {
+        let dir = crate::scratch("journal-changed");
+        let journal = dir.join("journal.toml");
+        let (src, dest) = moved(&dir, &journal, "a.txt", Duration::ZERO);
+        fs::write(&dest, "edited afterwards").unwrap();
+
+        let outcomes = Journal::new(&journal).undo(Undo::Last(1)).unwrap();
+        assert_eq!(outcomes[0].kind, OutcomeKind::Skipped);
+        assert_eq!(outcomes[0].message.as_deref(), Some("changed since"));
+        assert!(!src.exists());
+        assert_eq!(entries(&journal), 1);
+    }
+
+    #[test]
+    fn undo_restores_trashed_file() {
+        let dir = crate::scratch("journal-trash");
+        let journal = dir.join("journal.toml");
+        let src = dir.join("in/a.txt");
+        let trashed = dir.join("Trash/files/a.txt");
+        let info = dir.join("Trash/info/a.txt.trashinfo");
+        fs::create_dir_all(trashed.parent().unwrap()).unwrap();
+        fs::create_dir_all(info.parent().unwrap()).unwrap();
+        fs::write(&trashed, "a").unwrap();
+        fs::write(&info, "[Trash Info]").unwrap();
+        let entry = Entry::new(Op::Trash, &src, &trashed, &origin(&dir)).unwrap();
+        append(&journal, entry).unwrap();
+
+        let outcomes = Journal::new(&journal).undo(Undo::Last(1)).unwrap();
+        assert_eq!(outcomes[0].kind, OutcomeKind::Reverted);
+        assert_eq!(fs::read_to_string(&src).unwrap(), "a");
+        assert!(!trashed.exists() && !info.exists());
+        assert_eq!(entries(&journal), 0);
+    }
+
+    #[test]
+    fn undo_refuses_deleted_source() {
+        let dir = crate::scratch("journal-delete");
+        let journal = dir.join("journal.toml");
+        let src = dir.join("in/a.txt");
+        let entry = Entry::new(Op::Delete, &src, &src, &origin(&dir)).unwrap();
+        append(&journal, entry).unwrap();
+
+        let outcomes = Journal::new(&journal).undo(Undo::Last(1)).unwrap();
+        assert_eq!(outcomes[0].kind, OutcomeKind::Skipped);
+        assert_eq!(
+            outcomes[0].message.as_deref(),
+            Some("not revertible, deleted")
+        );
+        assert_eq!(entries(&journal), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn undo_keeps_names_which_are_not_unicode() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = crate::scratch("journal-raw");
+        let journal = dir.join("journal.toml");
+        let name = std::ffi::OsStr::from_bytes(b"bad\xff.jpg");
+        let (src, dest) = (dir.join("in").join(name), dir.join("out").join(name));
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::write(&dest, "raw").unwrap();
+        let entry = Entry::new(Op::Move, &src, &dest, &origin(&dir)).unwrap();
+        append(&journal, entry).unwrap();
+
+        let outcomes = Journal::new(&journal).undo(Undo::Last(1)).unwrap();
+        assert_eq!(outcomes[0].kind, OutcomeKind::Reverted);
+        assert_eq!(fs::read_to_string(&src).unwrap(), "raw");
+    }
+}
@@ -4,9 +4,37 @@ mod ruleset;
 pub use ruleset::*;
 
 mod watcher;
-pub use watcher::{Config, Watch, Msg};
+pub use watcher::{Config, Msg, Watch, WatchHandle};
+
+mod outcome;
+pub use outcome::{Outcome, OutcomeKind};
+
+mod command;
+pub use command::Command;
+
+mod explain;
+pub use explain::{Explanation, Step};
+
+mod event;
+pub use event::{EventFilter, Trigger};
+
+mod history;
+pub use history::History;
+
+mod journal;
+pub use journal::{Journal, Undo};
+
+mod rename;
+mod settle;
+mod template;
+mod trash;
+
+mod rules_file;
+pub use rules_file::RulesError;
+
+mod error;
+pub use error::{Error, Result};
 
-pub use notify::Result;
 pub use notify::EventKind;
 
 static IGNORED_COLOR: &str = "38;5;238";
@@ -32,32 +60,36 @@ enum ColorHelp {
 }
 
 impl ColoredPath for std::path::PathBuf {
+    /// Invalid unicode is replaced, see [`std::path::Path::to_string_lossy`].
     fn color_path(&self) -> String {
-        use std::path::Component::*;
         use ColorHelp::*;
+        use std::path::Component::*;
 
         let mut res = Vec::from([]);
         for (i, c) in self.components().rev().enumerate() {
             if let Some((h, s)) = match c {
-                CurDir => Some((Y, ".")),
-                ParentDir => Some((Y, "..")),
-                RootDir if i == 0 => Some((N, "#")),
+                CurDir => Some((Y, ".".into())),
+                ParentDir => Some((Y, "..".into())),
+                RootDir if i == 0 => Some((N, "#".into())),
                 // idx=0 do not color tail part
-                Normal(c) => Some((if i == 0 { N } else { Y }, c.to_str().unwrap())),
+                Normal(c) => Some((if i == 0 { N } else { Y }, c.to_string_lossy())),
                 // idx=1
-                Prefix(c) => Some((if i == 1 { M } else { Y }, c.as_os_str().to_str().unwrap())),
+                Prefix(c) => Some((if i == 1 { M } else { Y }, c.as_os_str().to_string_lossy())),
                 _ => None,
             } {
-                res.insert(0, if h == Y || h == M {
-                    let code = match i {
-                        _ if h == M => 37,
-                        1 => 36,
-                        _ => 37,
-                    };
-                    color!(code, s)
-                } else {
-                    s.to_string()
-                });
+                res.insert(
+                    0,
+                    if h == Y || h == M {
+                        let code = match i {
+                            _ if h == M => 37,
+                            1 => 36,
+                            _ => 37,
+                        };
+                        color!(code, s)
+                    } else {
+                        s.to_string()
+                    },
+                );
             };
         }
         res.join(&SEP)
@@ -71,3 +103,12 @@ fn timestamp() -> String {
         .as_secs()
         .to_string()
 }
+
+/// Empty directory under the system temp dir, unique per test and process.
+#[cfg(test)]
+pub(crate) fn scratch(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("watcher-{name}-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
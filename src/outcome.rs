@@ -0,0 +1,166 @@
+use notify::EventKind;
+
+use std::{fmt, path::PathBuf, time::SystemTime};
+
+use crate::{ColoredPath, IGNORED_COLOR, color};
+
+const ICON_NOTHING: &str = "";
+const ICON_INFO: &str = "";
+const ICON_SUCCESS: &str = " "; // 
+const ICON_WARNING: &str = "";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OutcomeKind {
+    /// Moved to its destination.
+    Moved,
+    Copied,
+    /// Hard or symbolic link created at destination.
+    Linked,
+    Deleted,
+    /// Moved to trash, `dest` is the trashed path.
+    Trashed,
+    /// Undone from the journal, `src` is where the path was moved back from.
+    Reverted,
+    /// Destination was taken, moved to [`crate::Config::dump_folder`] instead.
+    Diverted,
+    /// Nothing was done with the path.
+    Skipped,
+    /// Same content already exists at `dest`, see [`crate::Dedupe`].
+    Duplicate,
+    Info,
+    /// Something looks misconfigured, e.g. two tasks moving the same path.
+    Warning,
+    /// Reported by a [`crate::Module`] as done.
+    Success,
+    Error,
+}
+
+/// What happened to a single path, reported through [`crate::Msg`].
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub kind: OutcomeKind,
+    pub src: Option<PathBuf>,
+    pub dest: Option<PathBuf>,
+    pub message: Option<String>,
+    /// Label of the task which handled the path.
+    pub label: Option<String>,
+    /// Watched path of the ruleset which handled the path.
+    pub ruleset: Option<PathBuf>,
+    /// Event which triggered the task.
+    pub event: Option<EventKind>,
+    pub time: SystemTime,
+    /// Planned only, see [`crate::Config::dry_run`].
+    pub dry_run: bool,
+}
+
+impl Outcome {
+    pub fn new(kind: OutcomeKind) -> Self {
+        Self {
+            kind,
+            src: None,
+            dest: None,
+            message: None,
+            label: None,
+            ruleset: None,
+            event: None,
+            time: SystemTime::now(),
+            dry_run: false,
+        }
+    }
+
+    pub(crate) fn info(message: impl Into<String>) -> Self {
+        Self::new(OutcomeKind::Info).with_message(message)
+    }
+
+    pub(crate) fn error(message: impl Into<String>) -> Self {
+        Self::new(OutcomeKind::Error).with_message(message)
+    }
+
+    pub(crate) fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message.replace(message.into());
+        self
+    }
+
+    pub(crate) fn with_src(mut self, src: PathBuf) -> Self {
+        self.src.replace(src);
+        self
+    }
+
+    pub(crate) fn with_dest(mut self, dest: PathBuf) -> Self {
+        self.dest.replace(dest);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.kind == OutcomeKind::Error
+    }
+
+    /// Colored single line for terminal output.
+    pub fn colored(&self) -> String {
+        let src = self.src.as_ref().map(|p| p.color_path());
+        let dest = self.dest.as_ref().map(|p| p.color_path());
+        let message = self.message.clone().unwrap_or_default();
+
+        if self.dry_run {
+            let note = match self.message.as_ref() {
+                Some(msg) => color!(IGNORED_COLOR, format!(" ({msg})")),
+                None => String::new(),
+            };
+            let dest = match self.kind {
+                OutcomeKind::Deleted | OutcomeKind::Trashed => format!("{:?}", self.kind),
+                _ => dest.unwrap_or_default(),
+            };
+            return format!(
+                " {} {} {} -> {dest}{note}",
+                color!(37, ICON_INFO),
+                color!(33, "[dry-run]"),
+                src.unwrap_or_default(),
+            );
+        }
+
+        let (code, icon, msg) = match self.kind {
+            OutcomeKind::Moved => (32, ICON_SUCCESS, dest.unwrap_or(message)),
+            OutcomeKind::Reverted => (36, ICON_SUCCESS, dest.unwrap_or(message)),
+            OutcomeKind::Copied | OutcomeKind::Linked => {
+                (32, ICON_SUCCESS, dest.unwrap_or(message))
+            }
+            OutcomeKind::Deleted | OutcomeKind::Trashed => {
+                (32, ICON_SUCCESS, src.unwrap_or(message))
+            }
+            OutcomeKind::Diverted => (33, ICON_SUCCESS, dest.unwrap_or(message)),
+            OutcomeKind::Skipped => (33, ICON_NOTHING, src.unwrap_or(message)),
+            OutcomeKind::Duplicate => (33, ICON_NOTHING, dest.unwrap_or(message)),
+            OutcomeKind::Info => (37, ICON_INFO, message),
+            OutcomeKind::Warning => (33, ICON_WARNING, message),
+            OutcomeKind::Success => (32, ICON_SUCCESS, message),
+            OutcomeKind::Error => match src {
+                Some(src) => (31, ICON_WARNING, format!("{src}  {}", color!(31, message))),
+                None => (31, ICON_WARNING, message),
+            },
+        };
+        format!(" {} {msg}", color!(code, icon))
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.kind)?;
+        if self.dry_run {
+            f.write_str(" (dry-run)")?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, " [{label}]")?;
+        }
+        if let Some(src) = &self.src {
+            write!(f, " {}", src.display())?;
+        }
+        if let Some(dest) = &self.dest {
+            write!(f, " -> {}", dest.display())?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {message}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+use std::{
+    ffi::{OsStr, OsString},
+    sync::LazyLock,
+};
+
+static COPY_SUFFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s?\(\d+\)$").unwrap());
+
+/// Characters not allowed in file names on FAT and SMB shares.
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Single step of the rename pipeline of a [`crate::Task`].
+pub(crate) enum RenameStep {
+    ReplaceStem { pattern: Regex, with: String },
+    LowercaseExtension,
+    StripCopySuffix,
+    ReplaceInvalidChars(char),
+    Nfc,
+    Truncate(usize),
+}
+
+/// Runs every step over `name` in order. Names which are not valid unicode are
+/// left as they are, every step works on text.
+pub(crate) fn apply(steps: &[RenameStep], name: &OsStr) -> OsString {
+    let Some(name) = name.to_str() else {
+        return name.to_os_string();
+    };
+    let mut name = name.to_owned();
+    for step in steps {
+        let (stem, ext) = split(&name);
+        name = match step {
+            RenameStep::ReplaceStem { pattern, with } => {
+                join(&pattern.replace_all(stem, with.as_str()), ext)
+            }
+            RenameStep::LowercaseExtension => join(stem, ext.map(str::to_lowercase).as_deref()),
+            RenameStep::StripCopySuffix => join(&COPY_SUFFIX.replace(stem, ""), ext),
+            RenameStep::ReplaceInvalidChars(with) => {
+                let replaced: String = name
+                    .chars()
+                    .map(|c| match INVALID_CHARS.contains(&c) || c.is_control() {
+                        true => *with,
+                        false => c,
+                    })
+                    .collect();
+                // trailing dots and spaces are dropped by Windows
+                replaced.trim_end_matches(['.', ' ']).to_owned()
+            }
+            RenameStep::Nfc => name.nfc().collect(),
+            RenameStep::Truncate(max) => truncate(&name, *max),
+        };
+    }
+    OsString::from(name)
+}
+
+/// Splits at the last dot, names like `.bashrc` have no extension.
+fn split(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    }
+}
+
+fn join(stem: &str, ext: Option<&str>) -> String {
+    match ext {
+        Some(ext) => format!("{stem}.{ext}"),
+        None => stem.to_owned(),
+    }
+}
+
+/// Cuts the stem so that the whole name fits into `max` bytes, keeping the extension.
+fn truncate(name: &str, max: usize) -> String {
+    if name.len() <= max {
+        return name.to_owned();
+    }
+    let (stem, ext) = split(name);
+    let ext_len = ext.map_or(0, |e| e.len() + 1);
+    let Some(budget) = max.checked_sub(ext_len).filter(|b| *b > 0) else {
+        // extension alone is too long
+        return cut(name, max).to_owned();
+    };
+    join(cut(stem, budget), ext)
+}
+
+/// Longest prefix of at most `max` bytes ending on a char boundary.
+fn cut(s: &str, max: usize) -> &str {
+    let end = (0..=max.min(s.len()))
+        .rev()
+        .find(|i| s.is_char_boundary(*i))
+        .unwrap_or(0);
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamed(steps: &[RenameStep], name: &str) -> String {
+        apply(steps, OsStr::new(name)).into_string().unwrap()
+    }
+
+    #[test]
+    fn split_keeps_dotfiles_whole() {
+        assert_eq!(split(".bashrc"), (".bashrc", None));
+        assert_eq!(split("a.tar.gz"), ("a.tar", Some("gz")));
+        assert_eq!(split("Makefile"), ("Makefile", None));
+    }
+
+    #[test]
+    fn steps_run_in_order() {
+        let steps = [
+            RenameStep::StripCopySuffix,
+            RenameStep::LowercaseExtension,
+            RenameStep::ReplaceStem {
+                pattern: Regex::new(r"^IMG_").unwrap(),
+                with: "photo-".into(),
+            },
+        ];
+        assert_eq!(renamed(&steps, "IMG_0001 (2).JPG"), "photo-0001.jpg");
+        assert_eq!(renamed(&steps, ".bashrc"), ".bashrc");
+    }
+
+    #[test]
+    fn invalid_chars_are_replaced_and_trailing_dots_dropped() {
+        let steps = [RenameStep::ReplaceInvalidChars('_')];
+        assert_eq!(renamed(&steps, "a:b?c*.txt"), "a_b_c_.txt");
+        assert_eq!(renamed(&steps, "notes. "), "notes");
+    }
+
+    #[test]
+    fn nfc_composes() {
+        let steps = [RenameStep::Nfc];
+        assert_eq!(renamed(&steps, "cafe\u{301}.txt"), "caf\u{e9}.txt");
+    }
+
+    #[test]
+    fn truncate_keeps_extension_and_char_boundaries() {
+        assert_eq!(truncate("abcdef.txt", 8), "abcd.txt");
+        assert_eq!(truncate("short.txt", 255), "short.txt");
+        // `é` is two bytes, a cut inside it backs off
+        assert_eq!(truncate("ééé.txt", 8), "éé.txt");
+        assert_eq!(truncate("ab.verylongext", 5), "ab.ve");
+        assert_eq!(cut("é", 1), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_which_are_not_unicode_are_left_alone() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"bad\xff.JPG");
+        assert_eq!(apply(&[RenameStep::LowercaseExtension], name), name);
+    }
+}
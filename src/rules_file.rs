@@ -0,0 +1,423 @@
+use serde::Deserialize;
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    Action, Command, Config, ConflictPolicy, Dedupe, Error, Evaluation, EventFilter, Ruleset, Task,
+    Watch,
+};
+
+/// Rules loaded from a TOML file.
+///
+/// ```toml
+/// [config]
+/// dump_folder = "/home/me/__DUPLICATES__"
+/// poll_interval_ms = 2000
+///
+/// [[watch]]
+/// path = "/home/me/Downloads"
+/// recursive = false
+///
+/// [[watch.task]]
+/// label = "zips"
+/// event = "create"
+/// kind = "files"
+/// include = ["*.zip"]
+/// exclude = ["**/keep/*"]
+/// destination = "zips"
+/// conflict = "keep-both"
+/// rename = ["strip-copy-suffix", "lowercase-extension", { truncate = 255 }]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    config: ConfigEntry,
+    #[serde(default)]
+    watch: Vec<WatchEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigEntry {
+    dump_folder: Option<String>,
+    poll_interval_ms: Option<u64>,
+    tick_rate_ms: Option<u64>,
+    #[serde(default)]
+    dry_run: bool,
+    journal: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WatchEntry {
+    path: String,
+    #[serde(default)]
+    recursive: bool,
+    #[serde(default)]
+    scan_existing: bool,
+    poll_interval_ms: Option<u64>,
+    evaluation: Option<EvaluationEntry>,
+    /// Events kept per path, see [`Ruleset::event_history`].
+    history_size: Option<usize>,
+    history_ttl_ms: Option<u64>,
+    #[serde(default)]
+    task: Vec<TaskEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskEntry {
+    label: Option<String>,
+    priority: Option<i32>,
+    event: EventsEntry,
+    #[serde(default)]
+    kind: KindEntry,
+    pattern: Option<String>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    destination: Option<String>,
+    stable_ms: Option<u64>,
+    partial_suffixes: Option<Vec<String>>,
+    action: Option<ActionEntry>,
+    /// Program and arguments, e.g. `["gzip", "-k", "{src}"]`.
+    command: Option<Vec<String>>,
+    command_timeout_ms: Option<u64>,
+    conflict: Option<ConflictEntry>,
+    dedupe: Option<DedupeEntry>,
+    #[serde(default)]
+    rename: Vec<RenameEntry>,
+}
+
+/// e.g. `event = "create"` or `event = ["create", "modify"]`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum EventsEntry {
+    One(EventEntry),
+    Many(Vec<EventEntry>),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum EventEntry {
+    Create,
+    Modify,
+    Rename,
+    RenameInto,
+    Remove,
+    CloseWrite,
+    CreateThenModify,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum EvaluationEntry {
+    All,
+    FirstMatch,
+    Priority,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ActionEntry {
+    Move,
+    Copy,
+    Hardlink,
+    Symlink,
+    Delete,
+    Trash,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ConflictEntry {
+    Skip,
+    Overwrite,
+    KeepBoth,
+    Dump,
+    KeepNewer,
+    KeepLarger,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DedupeEntry {
+    Drop,
+    Dump,
+    Hardlink,
+}
+
+/// e.g. `rename = ["lowercase-extension", { truncate = 255 }]`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RenameEntry {
+    ReplaceInStem { pattern: String, with: String },
+    LowercaseExtension,
+    StripCopySuffix,
+    ReplaceInvalidChars(char),
+    Nfc,
+    Truncate(usize),
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum KindEntry {
+    Files,
+    Dirs,
+    #[default]
+    All,
+}
+
+/// Error while loading a rules file, with the offending entry if known.
+#[derive(Debug)]
+pub struct RulesError {
+    /// e.g. `watch[1].task[0]`
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+impl RulesError {
+    fn at(entry: impl Into<String>, message: impl fmt::Display) -> Self {
+        Self {
+            entry: Some(entry.into()),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.entry {
+            Some(entry) => write!(f, "{entry}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+impl Watch<'static> {
+    /// Build a watcher from a TOML rules file. See [`Watch::load_rules`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RulesError> {
+        let file = read_rules(path.as_ref())?;
+        let config = &file.config;
+        let mut watch = Watch::new(Config {
+            dump_folder: config.dump_folder.clone().unwrap_or_default().into(),
+            poll_interval: config.poll_interval_ms.map(Duration::from_millis),
+            tick_rate: config.tick_rate_ms.map(Duration::from_millis),
+            dry_run: config.dry_run,
+            journal: config.journal.clone().map(Into::into),
+        });
+        watch.add_rules(path.as_ref(), file)?;
+        Ok(watch)
+    }
+
+    /// Add `[[watch]]` entries of a TOML rules file. The `[config]` table is ignored.
+    ///
+    /// The file is watched while [`Watch::start`] runs, and on change the rulesets
+    /// loaded from it are replaced without restarting.
+    pub fn load_rules(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, RulesError> {
+        let file = read_rules(path.as_ref())?;
+        self.add_rules(path.as_ref(), file)?;
+        Ok(self)
+    }
+
+    fn add_rules(&mut self, path: &Path, file: RulesFile) -> Result<(), RulesError> {
+        let source = std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        for rule in build_rules(file, self.filter.as_deref(), &source)? {
+            self.push_rule(rule);
+        }
+
+        self.sources.push(source);
+        let filter = self.filter.clone();
+        self.reload.get_or_insert(Box::new(move |source| {
+            build_rules(read_rules(source)?, filter.as_deref(), source)
+        }));
+        Ok(())
+    }
+}
+
+fn build_rules(
+    file: RulesFile,
+    filter: Option<&str>,
+    source: &Path,
+) -> Result<Vec<Ruleset<'static>>, RulesError> {
+    // validate everything first so a bad entry leaves nothing half added
+    let mut entries = Vec::with_capacity(file.watch.len());
+    for (i, entry) in file.watch.into_iter().enumerate() {
+        let at = format!("watch[{i}]");
+        if entry.path.ends_with('*') {
+            let error = Error::AsteriskSuffix(entry.path.into());
+            return Err(RulesError::at(at, error));
+        }
+        let tasks = entry
+            .task
+            .iter()
+            .enumerate()
+            .map(|(j, task)| {
+                build_task(task).map_err(|e| RulesError::at(format!("{at}.task[{j}]"), e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.push((at, entry, tasks));
+    }
+
+    let mut rules = Vec::with_capacity(entries.len());
+    for (at, entry, tasks) in entries {
+        if filter.is_some_and(|f| !entry.path.contains(f)) {
+            continue;
+        }
+        let mut rule = match Ruleset::new(PathBuf::from(&entry.path)) {
+            Ok(rule) => rule,
+            Err(Error::PathNotFound(_)) => {
+                println!("\x1b[31m# skipping {}\x1b[0m", entry.path);
+                continue;
+            }
+            Err(error) => return Err(RulesError::at(at, error)),
+        };
+        if entry.recursive {
+            rule.recursive_mode();
+        }
+        if entry.scan_existing {
+            rule.scan_existing();
+        }
+        if let Some(ms) = entry.poll_interval_ms {
+            rule.with_poll_interval(Duration::from_millis(ms));
+        }
+        if entry.history_size.is_some() || entry.history_ttl_ms.is_some() {
+            let history = rule.history.get_mut().unwrap();
+            let size = entry.history_size.unwrap_or(history.size);
+            let ttl = entry
+                .history_ttl_ms
+                .map_or(history.ttl, Duration::from_millis);
+            rule.event_history(size, ttl);
+        }
+        if let Some(evaluation) = entry.evaluation {
+            rule.evaluation(match evaluation {
+                EvaluationEntry::All => Evaluation::All,
+                EvaluationEntry::FirstMatch => Evaluation::FirstMatch,
+                EvaluationEntry::Priority => Evaluation::Priority,
+            });
+        }
+        for task in &tasks {
+            rule.add(task);
+        }
+        rule.source = Some(source.to_path_buf());
+        rule.definition = Some(format!("{:?}", entry.task));
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+fn read_rules(path: &Path) -> Result<RulesFile, RulesError> {
+    let text = std::fs::read_to_string(path).map_err(|e| RulesError {
+        entry: None,
+        message: format!("{}: {e}", path.display()),
+    })?;
+    toml::from_str(&text).map_err(|e| RulesError {
+        entry: None,
+        message: format!("{}: {e}", path.display()),
+    })
+}
+
+fn build_task(entry: &TaskEntry) -> crate::Result<std::sync::Arc<std::sync::Mutex<Task<'static>>>> {
+    let mut task = Task::new();
+    if let Some(label) = &entry.label {
+        task = task.set_label(label.as_str());
+    }
+    if let Some(priority) = entry.priority {
+        task = task.set_priority(priority);
+    }
+    let events = match &entry.event {
+        EventsEntry::One(event) => std::slice::from_ref(event),
+        EventsEntry::Many(events) => events.as_slice(),
+    };
+    for event in events {
+        task = match event {
+            EventEntry::Create => task.on_create(),
+            EventEntry::Modify => task.on_modified(),
+            EventEntry::Rename => task.on_rename(),
+            EventEntry::RenameInto => task.event_watch(EventFilter::rename_into()),
+            EventEntry::Remove => task.on_remove(),
+            EventEntry::CloseWrite => task.on_close_write(),
+            EventEntry::CreateThenModify => task.event_watch(EventFilter::create_then_modify()),
+            EventEntry::Any => task.on_any(),
+        };
+    }
+    task = match entry.kind {
+        KindEntry::Files => task.watch_files(),
+        KindEntry::Dirs => task.watch_dirs(),
+        KindEntry::All => task.watch_all(),
+    };
+    if let Some(pattern) = &entry.pattern {
+        task = task.set_path_match_pattern(pattern);
+    }
+    for glob in &entry.include {
+        task = task.include(glob);
+    }
+    for glob in &entry.exclude {
+        task = task.exclude(glob);
+    }
+    if let Some(destination) = &entry.destination {
+        task = task.set_destination(destination);
+    }
+    if let Some(ms) = entry.stable_ms {
+        task = task.wait_until_stable(Duration::from_millis(ms));
+    }
+    if let Some(suffixes) = &entry.partial_suffixes {
+        let suffixes: Vec<&str> = suffixes.iter().map(String::as_str).collect();
+        task = task.set_partial_suffixes(&suffixes);
+    }
+    if let Some(action) = entry.action {
+        task = task.set_action(match action {
+            ActionEntry::Move => Action::Move,
+            ActionEntry::Copy => Action::Copy,
+            ActionEntry::Hardlink => Action::Hardlink,
+            ActionEntry::Symlink => Action::Symlink,
+            ActionEntry::Delete => Action::Delete,
+            ActionEntry::Trash => Action::Trash,
+        });
+    }
+    if let Some(command) = &entry.command {
+        let (program, args) = command.split_first().ok_or(Error::EmptyCommand)?;
+        let mut command = Command::new(program).args(args);
+        if let Some(ms) = entry.command_timeout_ms {
+            command = command.timeout(Duration::from_millis(ms));
+        }
+        task = task.run_command(command);
+    }
+    if let Some(conflict) = entry.conflict {
+        task = task.on_conflict(match conflict {
+            ConflictEntry::Skip => ConflictPolicy::Skip,
+            ConflictEntry::Overwrite => ConflictPolicy::Overwrite,
+            ConflictEntry::KeepBoth => ConflictPolicy::KeepBoth,
+            ConflictEntry::Dump => ConflictPolicy::DumpFolder,
+            ConflictEntry::KeepNewer => ConflictPolicy::KeepNewer,
+            ConflictEntry::KeepLarger => ConflictPolicy::KeepLarger,
+        });
+    }
+    if let Some(dedupe) = entry.dedupe {
+        task = task.dedupe(match dedupe {
+            DedupeEntry::Drop => Dedupe::Drop,
+            DedupeEntry::Dump => Dedupe::DumpFolder,
+            DedupeEntry::Hardlink => Dedupe::Hardlink,
+        });
+    }
+    for step in &entry.rename {
+        task = match step {
+            RenameEntry::ReplaceInStem { pattern, with } => task.replace_in_stem(pattern, with),
+            RenameEntry::LowercaseExtension => task.lowercase_extension(),
+            RenameEntry::StripCopySuffix => task.strip_copy_suffix(),
+            RenameEntry::ReplaceInvalidChars(c) => task.replace_invalid_chars(*c),
+            RenameEntry::Nfc => task.normalize_nfc(),
+            RenameEntry::Truncate(max) => task.truncate_name(*max),
+        };
+    }
+    task.build()
+}
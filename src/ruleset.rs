@@ -1,20 +1,72 @@
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
 use normalize_path::NormalizePath;
-use notify::{
-    EventKind, RecursiveMode,
-    event::{ModifyKind, RenameMode},
-};
-use regex::Regex;
+use notify::{EventKind, RecursiveMode};
+use regex::bytes::Regex;
 
 use std::{
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
+use crate::command::Command;
+use crate::event::{EventFilter, Trigger};
+use crate::explain::Trace;
+use crate::history::EventHistory;
+use crate::rename::{self, RenameStep};
 use crate::watcher::QueueTask;
+use crate::{Config, Error, Result, template};
 
+/// Decides what happens with a matched path. Implement [`Module::resolve_with`]
+/// to see the whole [`ModuleContext`], [`Module::resolve`] is called from it
+/// by default.
 #[must_use]
 pub trait Module: Sync + Send + 'static {
-    fn resolve(&mut self, src: PathBuf, dest: PathBuf) -> Resolved;
+    fn resolve(&mut self, _src: PathBuf, _dest: PathBuf) -> Resolved {
+        Resolved::Continue
+    }
+
+    fn resolve_with(&mut self, ctx: &ModuleContext) -> Resolved {
+        self.resolve(ctx.src.to_path_buf(), ctx.dest.to_path_buf())
+    }
+
+    /// Called when `dest` already exists and the task uses [`ConflictPolicy::Ask`].
+    fn on_conflict(&mut self, _src: &Path, _dest: &Path) -> ConflictPolicy {
+        ConflictPolicy::default()
+    }
+}
+
+/// Everything known about a path when its [`Module`] is asked.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ModuleContext<'c> {
+    pub src: &'c Path,
+    /// Destination after templates, before renames.
+    pub dest: &'c Path,
+    /// Event kind which matched the task.
+    pub kind: EventKind,
+    /// Event right before `kind` on the same path.
+    pub previous: Option<EventKind>,
+    pub history: &'c crate::History,
+    pub label: Option<&'c str>,
+    /// Watched path of the ruleset.
+    pub root: &'c Path,
+    /// [`crate::Config::dry_run`] is also set while [`crate::Watch::explain`]
+    /// asks, modules should not touch anything on disk then.
+    pub config: &'c crate::Config,
+    /// `None` once the path is removed.
+    pub metadata: Option<&'c std::fs::Metadata>,
+    /// Named captures of [`Task::set_path_match_pattern`].
+    pub captures: &'c [(String, String)],
+}
+
+impl ModuleContext<'_> {
+    pub fn capture(&self, name: &str) -> Option<&str> {
+        self.captures
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
 }
 
 /// Control flow.
@@ -24,16 +76,97 @@ pub enum Resolved {
     Move {
         dest: PathBuf,
     },
+    Copy {
+        dest: PathBuf,
+    },
+    Hardlink {
+        dest: PathBuf,
+    },
+    Symlink {
+        dest: PathBuf,
+    },
+    /// Remove the source.
+    Delete,
+    /// Move the source to trash.
+    Trash,
     Path(PathBuf),
     Info(String),
     Ok(String),
     Err(String),
-    /// Continue with default file move.
+    /// Pass to the next module of the chain, or continue with the task's action.
     Continue,
     #[default]
     None,
 }
 
+/// What a task does with a matched path.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Action {
+    #[default]
+    Move,
+    Copy,
+    /// Create a hard link at destination, files only.
+    Hardlink,
+    /// Create a symbolic link at destination pointing to the source.
+    Symlink,
+    /// Remove the source, destination is not used.
+    Delete,
+    /// Move the source to trash, following the XDG trash spec. Linux only.
+    Trash,
+}
+
+/// What to do when the destination already exists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConflictPolicy {
+    /// Leave the source where it is.
+    Skip,
+    /// Replace the destination. Like the other replacing policies, it skips when
+    /// only one of source and destination is a directory.
+    Overwrite,
+    /// Move under a free `name (1).ext` name.
+    KeepBoth,
+    /// Move to [`crate::Config::dump_folder`], adding a timestamp when taken as well.
+    #[default]
+    DumpFolder,
+    /// Replace the destination only when the source was modified later.
+    KeepNewer,
+    /// Replace the destination only when the source is larger.
+    KeepLarger,
+    /// Let [`Module::on_conflict`] of the last module in the chain decide.
+    Ask,
+}
+
+/// What to do with a file whose content is already in the destination directory,
+/// under any name. Compared by size first, then byte by byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Dedupe {
+    /// Remove the source.
+    Drop,
+    /// Move the source to [`crate::Config::dump_folder`].
+    DumpFolder,
+    /// Remove the source and hard link the existing file at destination instead,
+    /// as `name (1).ext` when the name is taken. When the existing file is the
+    /// destination itself, only the source is removed.
+    Hardlink,
+}
+
+/// How the tasks of a [`Ruleset`] are run against a single event.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Evaluation {
+    /// Every task in the order added. Once a task moves or removes the path,
+    /// every later one acting on it is skipped with a warning.
+    #[default]
+    All,
+    /// Stop at the first task which does anything with the path.
+    FirstMatch,
+    /// Like [`Evaluation::FirstMatch`], ordered by [`Task::set_priority`], highest first.
+    Priority,
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub(crate) enum WatchingKind {
     Files,
@@ -45,28 +178,48 @@ pub(crate) enum WatchingKind {
 // ----------------------------------------------------------------------------------
 //   - Task -
 // ----------------------------------------------------------------------------------
-type EventCheck = dyn Fn(EventKind, Option<EventKind>) -> bool + Send + Sync;
 
 #[must_use]
 #[derive(Default)]
 pub struct Task<'a> {
-    label: Option<&'a str>,
+    pub(crate) label: Option<String>,
     /// A [`WatchingKind`] to filter watch events
     pub(crate) watched_types: WatchingKind,
     /// [`EventKind`]
-    pub(crate) event_check: Option<&'a EventCheck>,
+    pub(crate) event_check: Option<EventFilter<'a>>,
     /// Destination path. By default watched path from [`Ruleset`] is used.
     pub(crate) destination: Option<PathBuf>,
-    /// Filter path events only if regex pattern match was provided.
+    /// Filter path events only if regex pattern match was provided. Matched against
+    /// raw path bytes, so paths which are not valid unicode can match too.
     match_pattern: Option<Regex>,
-
-    inner: Option<Arc<Mutex<dyn Module>>>,
+    /// Globs relative to the watched path, see [`Task::include`].
+    includes: Globs,
+    excludes: Globs,
+    /// Quiet period a file must keep the same size and mtime before it is queued.
+    pub(crate) stable_period: Option<Duration>,
+    /// Extensions of files still being written. Defaults to `part` on Windows.
+    partial_suffixes: Option<Vec<String>>,
+
+    /// Asked in order, see [`Task::with_module`].
+    modules: Vec<Arc<Mutex<dyn Module>>>,
+    action: Action,
+    /// Replaces the action, see [`Task::run_command`].
+    command: Option<Arc<Command>>,
+    conflict: ConflictPolicy,
+    dedupe: Option<Dedupe>,
+    /// Applied in order to the destination file name.
+    renames: Vec<RenameStep>,
+    /// See [`Evaluation::Priority`].
+    priority: i32,
+    /// First misconfiguration, reported by [`Task::build`] or when added to a [`Ruleset`].
+    error: Option<Error>,
 }
 
 pub(crate) struct InnerTask<'a> {
     pub(crate) task: Arc<Mutex<Task<'a>>>,
     /// Normalized path for moved files
     pub(crate) dest: PathBuf,
+    pub(crate) priority: i32,
 }
 
 impl<'a> Task<'a> {
@@ -74,25 +227,126 @@ impl<'a> Task<'a> {
         Self::default()
     }
 
+    fn fail(&mut self, error: Error) {
+        self.error.get_or_insert(error);
+    }
+
     pub fn set_path_match_pattern(mut self, m: &str) -> Self {
-        let a = Regex::new(m).unwrap();
-        self.match_pattern.replace(a);
+        match Regex::new(m) {
+            Ok(a) => _ = self.match_pattern.replace(a),
+            Err(err) => self.fail(err.into()),
+        }
+        self
+    }
+
+    /// Only handle paths matching any of the included globs, e.g. `*.zip` or
+    /// `**/invoices/*.pdf`. Can be called multiple times.
+    ///
+    /// Globs are matched relative to the watched path of the [`Ruleset`], `*` does not
+    /// cross directories. Globs without `/` match the file name at any depth.
+    pub fn include(mut self, glob: &str) -> Self {
+        if let Err(err) = self.includes.push(glob) {
+            self.fail(err.into());
+        }
+        self
+    }
+
+    /// Skip paths matching any of the excluded globs. See [`Task::include`].
+    pub fn exclude(mut self, glob: &str) -> Self {
+        if let Err(err) = self.excludes.push(glob) {
+            self.fail(err.into());
+        }
         self
     }
 
     /// Set destination path. Path can be relative.
+    ///
+    /// Path can be a template with placeholders rendered for each file:
+    /// - `{name}`, `{ext}` - file stem and extension
+    /// - `{parent}` - name of the directory containing the file
+    /// - `{mtime:%Y}`, `{now:%m}` - file modification or processing time, in
+    ///   [`chrono::format::strftime`] format
+    /// - `{capture}` - named capture of [`Task::set_path_match_pattern`]
+    ///
+    /// e.g. `photos/{mtime:%Y}/{mtime:%m}/{name}.{ext}`. When the last part uses
+    /// `{name}` or `{ext}` it names the file, otherwise the file name is kept.
+    /// Literal braces are written as `{{` and `}}`.
     pub fn set_destination(mut self, p: &str) -> Self {
         let path = PathBuf::from(p);
-        if !path.starts_with(".") && path.is_dir() {
-            std::fs::create_dir_all(&path).expect("created destination path");
+        if template::is_template(&path) {
+            if let Err(reason) = template::check(&path) {
+                self.fail(Error::Template {
+                    template: p.to_owned(),
+                    reason,
+                });
+            }
+        } else if !path.starts_with(".")
+            && path.is_dir()
+            && let Err(source) = std::fs::create_dir_all(&path)
+        {
+            self.fail(Error::Destination {
+                path: path.clone(),
+                source,
+            });
         }
         self.destination.replace(path);
         self
     }
 
+    /// Wait until the file keeps the same size and modification time for `d`
+    /// before it is handed over.
+    pub const fn wait_until_stable(mut self, d: Duration) -> Self {
+        self.stable_period = Some(d);
+        self
+    }
+
+    /// Ignore files with any of these extensions, e.g. `["part", "crdownload", "tmp"]`.
+    pub fn set_partial_suffixes(mut self, suffixes: &[&str]) -> Self {
+        let suffixes = suffixes
+            .iter()
+            .map(|s| s.trim_start_matches('.').to_owned())
+            .collect();
+        self.partial_suffixes.replace(suffixes);
+        self
+    }
+
+    /// Captures used by the destination template must exist in the match pattern.
+    fn check_template(&self) -> Result<()> {
+        let Some(dest) = self
+            .destination
+            .as_ref()
+            .filter(|d| template::is_template(d))
+        else {
+            return Ok(());
+        };
+        let names: Vec<_> = self
+            .match_pattern
+            .iter()
+            .flat_map(|re| re.capture_names().flatten())
+            .collect();
+        let captures = template::check(dest).unwrap_or_default();
+        match captures.iter().find(|c| !names.contains(&c.as_str())) {
+            Some(capture) => Err(Error::Template {
+                template: dest.to_string_lossy().into_owned(),
+                reason: format!("no `{capture}` capture in match pattern"),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn is_partial(&self, src: &Path) -> bool {
+        let Some(ext) = src.extension() else {
+            return false;
+        };
+        match &self.partial_suffixes {
+            Some(suffixes) => suffixes.iter().any(|s| ext.eq_ignore_ascii_case(s)),
+            None => cfg!(target_os = "windows") && ext == "part",
+        }
+    }
+
     /// For log printing?
-    pub fn set_label(mut self, s: &'a str) -> Self {
-        self.label.replace(s);
+    pub fn set_label(mut self, s: impl Into<String>) -> Self {
+        self.label.replace(s.into());
         self
     }
 
@@ -110,58 +364,273 @@ impl<'a> Task<'a> {
         self
     }
 
-    pub fn event_watch(mut self, f: &'a EventCheck) -> Self {
-        self.event_check.replace(f);
+    /// Run on events matching `filter`, see [`EventFilter`]. Calls accumulate, so
+    /// `.on_create().on_modified()` runs on either.
+    pub fn event_watch(mut self, filter: impl Into<EventFilter<'a>>) -> Self {
+        let filter = filter.into();
+        self.event_check = Some(match self.event_check.take() {
+            Some(earlier) => earlier.or(filter),
+            None => filter,
+        });
         self
     }
 
     pub fn on_modified(self) -> Self {
-        self.event_watch(&|kind, _| matches!(kind, EventKind::Modify(_)))
+        self.event_watch(EventFilter::modify())
     }
 
     pub fn on_create(self) -> Self {
-        self.event_watch(&|kind, _| matches!(kind, EventKind::Create(_)))
+        self.event_watch(EventFilter::create())
     }
 
     pub fn on_rename(self) -> Self {
-        self.event_watch(&|kind, _| {
-            matches!(
-                kind,
-                EventKind::Modify(ModifyKind::Name(RenameMode::To))
-                    | EventKind::Modify(ModifyKind::Name(RenameMode::Both))
-            )
-        })
+        self.event_watch(EventFilter::rename())
+    }
+
+    /// See [`EventFilter::remove`].
+    pub fn on_remove(self) -> Self {
+        self.event_watch(EventFilter::remove())
+    }
+
+    pub fn on_close_write(self) -> Self {
+        self.event_watch(EventFilter::close_write())
+    }
+
+    pub fn on_any(self) -> Self {
+        self.event_watch(EventFilter::always())
+    }
+
+    /// Set what happens with matched paths, [`Action::Move`] by default.
+    pub const fn set_action(mut self, action: Action) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Run `command` on matched paths instead of the action. The path is left
+    /// where it is, the rendered destination is only passed along as `{dest}`.
+    pub fn run_command(mut self, command: Command) -> Self {
+        if command.program.is_empty() {
+            self.fail(Error::EmptyCommand);
+        }
+        self.command.replace(Arc::new(command));
+        self
+    }
+
+    /// Tasks with higher priority run first in a ruleset using [`Evaluation::Priority`].
+    pub const fn set_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set what happens when the destination already exists.
+    pub const fn on_conflict(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict = policy;
+        self
     }
 
+    /// Check file content against the destination directory before moving.
+    pub const fn dedupe(mut self, mode: Dedupe) -> Self {
+        self.dedupe = Some(mode);
+        self
+    }
+
+    /// Rename on move: replace `pattern` matches in the file stem, `$1` and
+    /// `$name` refer to captures. Rename steps run in the order they were added,
+    /// after [`Module::resolve`]. Names which are not valid unicode are not renamed.
+    pub fn replace_in_stem(mut self, pattern: &str, with: &str) -> Self {
+        match regex::Regex::new(pattern) {
+            Ok(pattern) => self.renames.push(RenameStep::ReplaceStem {
+                pattern,
+                with: with.to_owned(),
+            }),
+            Err(err) => self.fail(err.into()),
+        }
+        self
+    }
+
+    /// Rename on move: `IMG.JPG` to `IMG.jpg`.
+    pub fn lowercase_extension(mut self) -> Self {
+        self.renames.push(RenameStep::LowercaseExtension);
+        self
+    }
+
+    /// Rename on move: drop ` (1)` suffix added by browsers to repeated downloads.
+    pub fn strip_copy_suffix(mut self) -> Self {
+        self.renames.push(RenameStep::StripCopySuffix);
+        self
+    }
+
+    /// Rename on move: replace characters invalid on FAT/SMB shares with `c` and
+    /// drop trailing dots and spaces.
+    pub fn replace_invalid_chars(mut self, c: char) -> Self {
+        self.renames.push(RenameStep::ReplaceInvalidChars(c));
+        self
+    }
+
+    /// Rename on move: Unicode NFC normalization.
+    pub fn normalize_nfc(mut self) -> Self {
+        self.renames.push(RenameStep::Nfc);
+        self
+    }
+
+    /// Rename on move: shorten the stem so the name fits into `max` bytes,
+    /// usually 255.
+    pub fn truncate_name(mut self, max: usize) -> Self {
+        self.renames.push(RenameStep::Truncate(max));
+        self
+    }
+
+    /// Append `module` to the chain, modules are asked in the order added. Each
+    /// sees the destination and action left by the previous one and may replace
+    /// them or pass with [`Resolved::Continue`]. Messages and [`Resolved::None`]
+    /// end the chain. On [`ConflictPolicy::Ask`] the last module decides.
     pub fn with_module<T: Module>(mut self, module: T) -> Self {
-        self.inner.replace(Arc::new(Mutex::new(module)));
+        self.modules.push(Arc::new(Mutex::new(module)));
         self
     }
 
+    /// Errors are reported when the task is added to a [`Ruleset`], use [`Task::build`]
+    /// to check them here.
     pub fn finish(self) -> Arc<Mutex<Self>> {
         Arc::new(Mutex::new(self))
     }
 
-    pub(crate) fn parse(&self, src: PathBuf, mut dest: PathBuf) -> QueueTask {
-        if !src.exists()
-            || cfg!(target_os = "windows") && src.extension().is_some_and(|e| e == "part")
-        {
+    /// Like [`Task::finish`], but fails on misconfiguration.
+    pub fn build(self) -> Result<Arc<Mutex<Self>>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.check_template()?;
+        if self.event_check.is_none() {
+            return Err(Error::MissingEvent { label: self.label });
+        }
+        Ok(self.finish())
+    }
+
+    /// `dest` is the destination joined with the watched path of the ruleset.
+    pub(crate) fn parse(&self, trigger: &Trigger, dest: PathBuf, config: &Config) -> QueueTask {
+        self.parse_traced(trigger, dest, config, &mut Trace::off())
+    }
+
+    /// [`Task::parse`] recording each decision, see [`crate::Watch::explain`].
+    pub(crate) fn parse_traced(
+        &self,
+        trigger: &Trigger,
+        mut dest: PathBuf,
+        config: &Config,
+        trace: &mut Trace,
+    ) -> QueueTask {
+        let src = trigger.path.to_path_buf();
+        let removed = matches!(trigger.kind, EventKind::Remove(_));
+        trace.step("exists", src.exists() || removed, || match removed {
+            true => "removed".into(),
+            false => String::new(),
+        });
+        if !src.exists() && !removed {
+            return QueueTask::None;
+        }
+        let partial = self.is_partial(&src);
+        trace.step("partial", !partial, || match partial {
+            true => "still being written".into(),
+            false => String::new(),
+        });
+        if partial {
             return QueueTask::None;
         }
 
-        if let Some(re) = &self.match_pattern {
-            if re.captures(src.to_str().unwrap()).is_none() {
+        let relative = src.strip_prefix(trigger.root).unwrap_or(&src);
+        if !self.includes.is_empty() {
+            let included = self.includes.is_match(relative);
+            trace.step("include", included, || relative.display().to_string());
+            if !included {
                 return QueueTask::None;
             }
         }
+        if self.excludes.is_match(relative) {
+            trace.step("exclude", false, || relative.display().to_string());
+            return QueueTask::None;
+        }
 
-        dest.push(src.file_name().unwrap());
+        let mut captures = Vec::new();
+        if let Some(re) = &self.match_pattern {
+            let Some(caps) = re.captures(src.as_os_str().as_encoded_bytes()) else {
+                trace.step("pattern", false, || re.as_str().to_owned());
+                return QueueTask::None;
+            };
+            for name in re.capture_names().flatten() {
+                if let Some(value) = caps.name(name) {
+                    let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                    captures.push((name.to_owned(), value));
+                }
+            }
+            trace.step("pattern", true, || {
+                let captures: Vec<_> = captures.iter().map(|(k, v)| format!("{k}={v}")).collect();
+                format!("{} {}", re.as_str(), captures.join(" "))
+            });
+        }
 
-        if let Some(x) = &self.inner {
-            match x.lock().unwrap().resolve(src.clone(), dest.clone()) {
-                Resolved::Move { dest: mut new_path } => {
-                    std::mem::swap(&mut dest, &mut new_path);
+        // only the task's own destination is a template, not the watched path
+        if let Some(template) = self
+            .destination
+            .as_ref()
+            .filter(|d| template::is_template(d))
+        {
+            let ctx = template::Context {
+                src: &src,
+                captures: &captures,
+                now: std::time::SystemTime::now(),
+            };
+            let names_file = template::names_file(template);
+            match template::render(template, &ctx) {
+                Ok(rendered) => dest = trigger.root.join(rendered).normalize(),
+                Err(err) => {
+                    trace.step("destination", false, || err.clone());
+                    return QueueTask::Err(format!("{}: {err}", src.display()));
                 }
+            }
+            if !names_file {
+                dest.push(src.file_name().unwrap());
+            }
+        } else {
+            dest.push(src.file_name().unwrap());
+        }
+        trace.step("destination", true, || dest.display().to_string());
+
+        let mut action = self.action;
+        let metadata = match self.modules.is_empty() {
+            true => None,
+            false => src.metadata().ok(),
+        };
+        for module in &self.modules {
+            let ctx = ModuleContext {
+                src: &src,
+                dest: &dest,
+                kind: trigger.kind,
+                previous: trigger.history.previous(),
+                history: trigger.history,
+                label: self.label.as_deref(),
+                root: trigger.root,
+                config,
+                metadata: metadata.as_ref(),
+                captures: &captures,
+            };
+            let resolved = module.lock().unwrap().resolve_with(&ctx);
+            trace.step(
+                "module",
+                !matches!(resolved, Resolved::None | Resolved::Err(_)),
+                || format!("{resolved:?}"),
+            );
+            match resolved {
+                Resolved::Move { dest: new_path } => (dest, action) = (new_path, Action::Move),
+                Resolved::Copy { dest: new_path } => (dest, action) = (new_path, Action::Copy),
+                Resolved::Hardlink { dest: new_path } => {
+                    (dest, action) = (new_path, Action::Hardlink)
+                }
+                Resolved::Symlink { dest: new_path } => {
+                    (dest, action) = (new_path, Action::Symlink)
+                }
+                Resolved::Delete => action = Action::Delete,
+                Resolved::Trash => action = Action::Trash,
                 Resolved::Path(path) => return QueueTask::Path(path),
                 Resolved::Info(msg) => return QueueTask::Info(msg),
                 Resolved::Ok(msg) => return QueueTask::Ok(msg),
@@ -171,13 +640,99 @@ impl<'a> Task<'a> {
             }
         }
 
+        if removed {
+            // nothing left to move, only a command may still act on it
+            let Some(command) = &self.command else {
+                trace.step("action", false, || "path is gone".into());
+                return QueueTask::None;
+            };
+            trace.step("action", true, || command.line(&src, &dest));
+            return QueueTask::Run {
+                src,
+                dest,
+                command: Arc::clone(command),
+            };
+        }
+
+        if matches!(action, Action::Delete | Action::Trash) {
+            trace.step("action", true, || format!("{action:?}"));
+            return QueueTask::Remove {
+                src,
+                trash: action == Action::Trash,
+            };
+        }
+
+        if !self.renames.is_empty()
+            && let Some(name) = dest.file_name()
+        {
+            let renamed = rename::apply(&self.renames, name);
+            if !renamed.is_empty() {
+                dest.set_file_name(renamed);
+            }
+            trace.step("rename", true, || dest.display().to_string());
+        }
+
+        if let Some(command) = &self.command {
+            trace.step("action", true, || command.line(&src, &dest));
+            return QueueTask::Run {
+                src,
+                dest,
+                command: Arc::clone(command),
+            };
+        }
+
         if src.cmp(&dest) == std::cmp::Ordering::Equal {
             // if paths are the same, as that will obviously should do nothing
+            trace.step("same path", false, String::new);
             return QueueTask::None;
         }
 
         assert!(&dest.file_stem().is_some(), "filename can't be missing");
-        QueueTask::Move { src, dest }
+        trace.step("action", true, || format!("{action:?}"));
+        QueueTask::Move {
+            src,
+            dest,
+            action,
+            conflict: self.conflict,
+            dedupe: self.dedupe,
+            module: self.modules.last().cloned(),
+        }
+    }
+}
+
+/// Globs split by whether they match the file name or the relative path.
+#[derive(Default)]
+struct Globs {
+    names: Vec<Glob>,
+    names_set: GlobSet,
+    paths: Vec<Glob>,
+    paths_set: GlobSet,
+}
+
+impl Globs {
+    fn push(&mut self, glob: &str) -> std::result::Result<(), globset::Error> {
+        let with_path = glob.contains('/');
+        let glob = GlobBuilder::new(glob).literal_separator(true).build()?;
+        let (globs, set) = match with_path {
+            true => (&mut self.paths, &mut self.paths_set),
+            false => (&mut self.names, &mut self.names_set),
+        };
+        globs.push(glob);
+        let mut builder = GlobSetBuilder::new();
+        globs.iter().for_each(|g| _ = builder.add(g.clone()));
+        *set = builder.build()?;
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.paths.is_empty()
+    }
+
+    fn is_match(&self, relative: &Path) -> bool {
+        relative
+            .file_name()
+            .is_some_and(|name| self.names_set.is_match(name))
+            || self.paths_set.is_match(relative)
     }
 }
 
@@ -187,28 +742,44 @@ impl<'a> Task<'a> {
 #[must_use]
 pub struct Ruleset<'a> {
     pub(crate) watched_path: PathBuf,
-    pub(crate) tasks: Vec<InnerTask<'a>>,
+    /// Swapped in place when rules are reloaded.
+    pub(crate) tasks: RwLock<Vec<InnerTask<'a>>>,
     pub(crate) poll_interval: Option<std::time::Duration>,
     pub(crate) recursive_mode: RecursiveMode,
+    /// Process entries already present in the watched path on start.
+    pub(crate) scan_existing: bool,
+    /// Rules file this ruleset was loaded from.
+    pub(crate) source: Option<PathBuf>,
+    /// Tasks as written in the rules file, tells whether a reload changed them.
+    pub(crate) definition: Option<String>,
+    /// Misconfigured tasks, reported by [`crate::Watch::start`].
+    pub(crate) errors: Vec<Error>,
+    pub(crate) evaluation: Evaluation,
+    /// Recent events per path, handed to event checks.
+    pub(crate) history: Mutex<EventHistory>,
 }
 
 impl<'a> Ruleset<'a> {
-    pub fn new(watched_path: PathBuf) -> notify::Result<Self> {
-        if watched_path.to_owned().ends_with("*") {
-            panic!(
-                "Asterisk (*) not allowed as suffix in path. Use recursive_mode() method instead."
-            );
+    pub fn new(watched_path: PathBuf) -> Result<Self> {
+        if watched_path.ends_with("*") {
+            return Err(Error::AsteriskSuffix(watched_path));
         }
 
         if !watched_path.exists() {
-            return Err(notify::Error::path_not_found());
+            return Err(Error::PathNotFound(watched_path));
         }
 
         Ok(Self {
             watched_path,
             recursive_mode: RecursiveMode::NonRecursive,
-            tasks: Vec::new(),
+            tasks: RwLock::default(),
             poll_interval: None,
+            scan_existing: false,
+            source: None,
+            definition: None,
+            errors: Vec::new(),
+            evaluation: Evaluation::default(),
+            history: Mutex::default(),
         })
     }
 
@@ -224,22 +795,78 @@ impl<'a> Ruleset<'a> {
         self
     }
 
+    /// Run entries already present in the watched path through tasks on start,
+    /// as if they were just created.
+    pub fn scan_existing(&mut self) -> &mut Self {
+        self.scan_existing = true;
+        self
+    }
+
+    /// Keep up to `size` events per path for [`crate::History`], forgetting those older
+    /// than `ttl`. Defaults to 8 events and one minute, zero size disables it.
+    pub fn event_history(&mut self, size: usize, ttl: Duration) -> &mut Self {
+        let history = self.history.get_mut().unwrap();
+        history.size = size;
+        history.ttl = ttl;
+        self
+    }
+
+    /// Set how tasks are run against an event, [`Evaluation::All`] by default.
+    pub fn evaluation(&mut self, mode: Evaluation) -> &mut Self {
+        self.evaluation = mode;
+        self
+    }
+
+    /// Task indices in evaluation order.
+    /// Everything but the tasks, a reload restarts the watcher when these differ.
+    pub(crate) fn same_settings(&self, other: &Ruleset) -> bool {
+        let history = |r: &Ruleset| {
+            let history = r.history.lock().unwrap();
+            (history.size, history.ttl)
+        };
+        self.recursive_mode == other.recursive_mode
+            && self.poll_interval == other.poll_interval
+            && self.scan_existing == other.scan_existing
+            && self.evaluation == other.evaluation
+            && history(self) == history(other)
+    }
+
+    pub(crate) fn order(&self, tasks: &[InnerTask]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..tasks.len()).collect();
+        if self.evaluation == Evaluation::Priority {
+            // stable, equal priorities keep the order added
+            order.sort_by_key(|i| std::cmp::Reverse(tasks[*i].priority));
+        }
+        order
+    }
+
     pub fn finish(&self) -> Arc<&Self> {
         Arc::new(self)
     }
 
+    /// Misconfigured tasks are skipped and reported by [`crate::Watch::start`].
     pub fn add(&mut self, task: &Arc<Mutex<Task<'a>>>) -> &mut Self {
         let watched_path = self.watched_path.clone();
         let mut dest: Option<_> = None;
+        let mut priority = 0;
         // adjust task to parent rule
         _ = task.lock().is_ok_and(|task| {
+            if let Some(error) = &task.error {
+                self.errors.push(error.clone());
+                return false;
+            }
+            if let Err(error) = task.check_template() {
+                self.errors.push(error);
+                return false;
+            }
             if task.event_check.is_none() {
-                panic!(
-                    "required watch event for {} task missing ",
-                    task.label.unwrap()
-                );
+                self.errors.push(Error::MissingEvent {
+                    label: task.label.clone(),
+                });
+                return false;
             }
 
+            priority = task.priority;
             dest.replace(match &task.destination {
                 Some(dst) => watched_path.join(dst),
                 // inherit from parent if empty
@@ -249,10 +876,13 @@ impl<'a> Ruleset<'a> {
             true
         });
 
-        self.tasks.push(InnerTask {
-            task: Arc::clone(task),
-            dest: dest.expect("could not resolve path").normalize(),
-        });
+        if let Some(dest) = dest {
+            self.tasks.get_mut().unwrap().push(InnerTask {
+                task: Arc::clone(task),
+                dest: dest.normalize(),
+                priority,
+            });
+        }
         self
     }
 }
@@ -279,3 +909,46 @@ impl<'a> Ruleset<'a> {
 //         f(&self.inner.lock().unwrap())
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(patterns: &[&str]) -> Globs {
+        let mut globs = Globs::default();
+        for pattern in patterns {
+            globs.push(pattern).unwrap();
+        }
+        globs
+    }
+
+    #[test]
+    fn globs_without_slash_match_file_names_at_any_depth() {
+        let globs = globs(&["*.zip"]);
+        assert!(globs.is_match(Path::new("a.zip")));
+        assert!(globs.is_match(Path::new("sub/deeper/a.zip")));
+        assert!(!globs.is_match(Path::new("a.zip/readme.txt")));
+    }
+
+    #[test]
+    fn globs_with_slash_match_relative_paths() {
+        let globs = globs(&["keep/*"]);
+        assert!(globs.is_match(Path::new("keep/a.zip")));
+        assert!(!globs.is_match(Path::new("sub/keep/a.zip")));
+        assert!(!globs.is_match(Path::new("keep")));
+
+        let globs = self::globs(&["**/keep/*"]);
+        assert!(globs.is_match(Path::new("sub/keep/a.zip")));
+        assert!(!globs.is_match(Path::new("sub/keep/deeper/a.zip")));
+    }
+
+    #[test]
+    fn globs_mix_names_and_paths() {
+        let globs = globs(&["*.tmp", "cache/**"]);
+        assert!(!globs.is_empty());
+        assert!(globs.is_match(Path::new("sub/x.tmp")));
+        assert!(globs.is_match(Path::new("cache/a/b.txt")));
+        assert!(!globs.is_match(Path::new("sub/b.txt")));
+        assert!(Globs::default().is_empty());
+    }
+}
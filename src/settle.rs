@@ -0,0 +1,114 @@
+use notify::EventKind;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{History, Task, Trigger};
+
+/// Size and modification time, `None` once the path is gone.
+type Snapshot = Option<(u64, Option<SystemTime>)>;
+
+fn snapshot(path: &Path) -> Snapshot {
+    fs::metadata(path)
+        .ok()
+        .map(|m| (m.len(), m.modified().ok()))
+}
+
+/// Event held back until its path stops changing, see [`crate::Task::wait_until_stable`].
+pub(crate) struct Unsettled<'a> {
+    pub(crate) kind: EventKind,
+    pub(crate) from: Option<PathBuf>,
+    pub(crate) history: History,
+    /// Task which asked to wait, dispatching resumes at it.
+    pub(crate) task: Arc<Mutex<Task<'a>>>,
+    /// Label of the task which claimed the path before the wait.
+    pub(crate) claimed: Option<Option<String>>,
+    period: Duration,
+    snapshot: Snapshot,
+    since: Instant,
+}
+
+impl Unsettled<'_> {
+    /// How long the path kept the same size and modification time.
+    pub(crate) fn settled_for(&self) -> Duration {
+        self.since.elapsed()
+    }
+}
+
+/// Paths of one ruleset waiting to settle. Checked by its watcher thread
+/// between events, so a wait never holds up stopping or reloading.
+#[derive(Default)]
+pub(crate) struct Settling<'a> {
+    paths: HashMap<PathBuf, Unsettled<'a>>,
+}
+
+impl<'a> Settling<'a> {
+    /// Holds `trigger` back for `period`. A path already waiting keeps its
+    /// first event.
+    pub(crate) fn wait(
+        &mut self,
+        trigger: &Trigger,
+        task: Arc<Mutex<Task<'a>>>,
+        claimed: Option<Option<String>>,
+        period: Duration,
+    ) {
+        if self.paths.contains_key(trigger.path) {
+            return;
+        }
+        self.paths.insert(
+            trigger.path.to_path_buf(),
+            Unsettled {
+                kind: trigger.kind,
+                from: trigger.from.map(Path::to_path_buf),
+                history: trigger.history.clone(),
+                task,
+                claimed,
+                period,
+                snapshot: snapshot(trigger.path),
+                since: Instant::now(),
+            },
+        );
+    }
+
+    /// Time until the earliest wait is due, `None` when nothing waits.
+    pub(crate) fn next_check(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.paths
+            .values()
+            .map(|u| (u.since + u.period).saturating_duration_since(now))
+            .min()
+    }
+
+    /// Takes out paths which kept still for their period. Paths which changed
+    /// wait again, gone ones are dropped.
+    pub(crate) fn settled(&mut self) -> Vec<(PathBuf, Unsettled<'a>)> {
+        let due: Vec<PathBuf> = self
+            .paths
+            .iter()
+            .filter(|(_, u)| u.since.elapsed() >= u.period)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut settled = Vec::new();
+        for path in due {
+            let next = snapshot(&path);
+            match self.paths.get_mut(&path) {
+                _ if next.is_none() => _ = self.paths.remove(&path),
+                Some(unsettled) if unsettled.snapshot != next => {
+                    unsettled.snapshot = next;
+                    unsettled.since = Instant::now();
+                }
+                _ => {
+                    let unsettled = self.paths.remove(&path).expect("due path");
+                    settled.push((path, unsettled));
+                }
+            }
+        }
+        settled
+    }
+}
@@ -0,0 +1,261 @@
+use chrono::{DateTime, Local};
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Values available to `{...}` placeholders of a destination template.
+pub(crate) struct Context<'a> {
+    pub(crate) src: &'a Path,
+    /// Named captures of the task's match pattern.
+    pub(crate) captures: &'a [(String, String)],
+    pub(crate) now: SystemTime,
+}
+
+/// `true` when `path` contains any `{...}` placeholder.
+pub(crate) fn is_template(path: &Path) -> bool {
+    path.to_string_lossy().contains('{')
+}
+
+/// `true` when the last component names the file, i.e. uses `{name}` or `{ext}`.
+pub(crate) fn names_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| name.contains("{name}") || name.contains("{ext}"))
+}
+
+/// Checks placeholder syntax and date formats, returning the names of captures
+/// the template depends on.
+pub(crate) fn check(path: &Path) -> Result<Vec<String>, String> {
+    let mut captures = Vec::new();
+    for component in path.components() {
+        let part = component.as_os_str().to_string_lossy();
+        for key in keys(&part)? {
+            match key.split_once(':') {
+                Some(("mtime" | "now", format)) => {
+                    let mut out = String::new();
+                    write!(out, "{}", Local::now().format(format))
+                        .map_err(|_| format!("invalid date format `{format}`"))?;
+                }
+                Some((name, _)) => return Err(format!("unknown placeholder `{name}`")),
+                None if matches!(key, "name" | "ext" | "parent") => {}
+                None => captures.push(key.to_owned()),
+            }
+        }
+    }
+    Ok(captures)
+}
+
+/// Replaces placeholders in every component of `template`.
+pub(crate) fn render(template: &Path, ctx: &Context) -> Result<PathBuf, String> {
+    let mut path = PathBuf::new();
+    for component in template.components() {
+        let Component::Normal(part) = component else {
+            path.push(component);
+            continue;
+        };
+        let Some(part) = part.to_str().filter(|p| p.contains('{')) else {
+            path.push(part);
+            continue;
+        };
+        let rendered = render_part(part, ctx)?;
+        if rendered.is_empty() {
+            return Err(format!("`{part}` rendered empty"));
+        }
+        path.push(rendered);
+    }
+    Ok(path)
+}
+
+/// Built from pieces, as `{name}` and `{ext}` keep file names which are not UTF-8.
+fn render_part(part: &str, ctx: &Context) -> Result<OsString, String> {
+    let mut pieces: Vec<OsString> = Vec::new();
+    let mut rest = part;
+    while let Some(start) = rest.find(['{', '}']) {
+        pieces.push(rest[..start].into());
+        let tail = &rest[start..];
+        if let Some(tail) = tail.strip_prefix("{{") {
+            pieces.push("{".into());
+            rest = tail;
+            continue;
+        }
+        if let Some(tail) = tail.strip_prefix("}}") {
+            pieces.push("}".into());
+            rest = tail;
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err("unmatched `}`".into());
+        }
+        let end = tail.find('}').ok_or("unclosed `{`")?;
+        pieces.push(value(&tail[1..end], ctx)?);
+        rest = &tail[end + 1..];
+    }
+    pieces.push(rest.into());
+
+    // `{name}.{ext}` of a file without extension
+    while let Some(last) = pieces.last_mut() {
+        let Some(trimmed) = last.to_str().map(|s| s.trim_end_matches('.')) else {
+            break;
+        };
+        match trimmed.is_empty() {
+            true => _ = pieces.pop(),
+            false => {
+                *last = trimmed.to_owned().into();
+                break;
+            }
+        }
+    }
+    Ok(pieces.into_iter().fold(OsString::new(), |mut out, piece| {
+        out.push(piece);
+        out
+    }))
+}
+
+fn value(key: &str, ctx: &Context) -> Result<OsString, String> {
+    let raw = |s: Option<&OsStr>| s.unwrap_or_default().to_os_string();
+    let date = |time: SystemTime, format: &str| {
+        let mut out = String::new();
+        write!(out, "{}", DateTime::<Local>::from(time).format(format))
+            .map(|_| OsString::from(out))
+            .map_err(|_| format!("invalid date format `{format}`"))
+    };
+
+    Ok(match key.split_once(':') {
+        Some(("mtime", format)) => {
+            let mtime = ctx.src.metadata().and_then(|m| m.modified());
+            date(mtime.map_err(|e| e.to_string())?, format)?
+        }
+        Some(("now", format)) => date(ctx.now, format)?,
+        Some((name, _)) => return Err(format!("unknown placeholder `{name}`")),
+        None => match key {
+            "name" => raw(ctx.src.file_stem()),
+            "ext" => raw(ctx.src.extension()),
+            "parent" => raw(ctx.src.parent().and_then(Path::file_name)),
+            capture => ctx
+                .captures
+                .iter()
+                .find(|(name, _)| name == capture)
+                .map(|(_, value)| value.into())
+                .ok_or(format!("capture `{capture}` did not match"))?,
+        },
+    })
+}
+
+/// Placeholder keys of a single component, without braces.
+fn keys(part: &str) -> Result<Vec<&str>, String> {
+    let mut keys = Vec::new();
+    let mut rest = part;
+    while let Some(start) = rest.find(['{', '}']) {
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err(format!("unmatched `}}` in `{part}`"));
+        }
+        let end = tail.find('}').ok_or(format!("unclosed `{{` in `{part}`"))?;
+        let key = &tail[1..end];
+        if key.is_empty() || key.contains('{') {
+            return Err(format!("invalid placeholder in `{part}`"));
+        }
+        keys.push(key);
+        rest = &tail[end + 1..];
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn render_for(template: &str, src: &str, captures: &[(&str, &str)]) -> Result<PathBuf, String> {
+        let captures: Vec<_> = captures
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let ctx = Context {
+            src: Path::new(src),
+            captures: &captures,
+            // mid June 2024, the same year in every time zone
+            now: UNIX_EPOCH + Duration::from_secs(1_718_452_800),
+        };
+        render(Path::new(template), &ctx)
+    }
+
+    #[test]
+    fn check_lists_captures() {
+        let captures = check(Path::new("{year}/{name}-{album}.{ext}")).unwrap();
+        assert_eq!(captures, ["year", "album"]);
+        assert!(check(Path::new("{now:%Y}/{parent}")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn check_rejects_bad_placeholders() {
+        assert!(check(Path::new("{size:big}")).is_err());
+        assert!(check(Path::new("{name")).is_err());
+        assert!(check(Path::new("name}")).is_err());
+        assert!(check(Path::new("{}")).is_err());
+        assert!(check(Path::new("{now:%Q}")).is_err());
+    }
+
+    #[test]
+    fn render_replaces_placeholders() {
+        let path = render_for("{parent}/{now:%Y}/{name}.{ext}", "/in/photos/a.jpg", &[]).unwrap();
+        assert_eq!(path, Path::new("photos/2024/a.jpg"));
+        let path = render_for("/out/{album}", "/in/a.mp3", &[("album", "x")]).unwrap();
+        assert_eq!(path, Path::new("/out/x"));
+    }
+
+    #[test]
+    fn render_unescapes_braces() {
+        let path = render_for("{{raw}}-{name}", "/in/a.txt", &[]).unwrap();
+        assert_eq!(path, Path::new("{raw}-a"));
+    }
+
+    #[test]
+    fn render_trims_dot_of_missing_extension() {
+        let path = render_for("out/{name}.{ext}", "/in/Makefile", &[]).unwrap();
+        assert_eq!(path, Path::new("out/Makefile"));
+        assert!(render_for("{ext}", "/in/Makefile", &[]).is_err());
+    }
+
+    #[test]
+    fn render_reports_missing_capture() {
+        let err = render_for("{year}", "/in/a.txt", &[]).unwrap_err();
+        assert_eq!(err, "capture `year` did not match");
+    }
+
+    #[test]
+    fn names_file_only_for_name_or_ext() {
+        assert!(names_file(Path::new("out/{name}.{ext}")));
+        assert!(names_file(Path::new("out/{ext}")));
+        assert!(!names_file(Path::new("{name}/out")));
+        assert!(is_template(Path::new("out/{year}")));
+        assert!(!is_template(Path::new("out/year")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn render_keeps_names_which_are_not_unicode() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let src = Path::new("/in").join(OsStr::from_bytes(b"bad\xff.jpg"));
+        let ctx = Context {
+            src: &src,
+            captures: &[],
+            now: SystemTime::now(),
+        };
+        let path = render(Path::new("out/{name}.{ext}"), &ctx).unwrap();
+        assert_eq!(
+            path,
+            Path::new("out").join(OsStr::from_bytes(b"bad\xff.jpg"))
+        );
+    }
+}
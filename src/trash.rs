@@ -0,0 +1,85 @@
+//! Home trash of the [freedesktop.org trash spec](https://specifications.freedesktop.org/trash-spec/latest/).
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Moves `path` to the home trash, returning its new location.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn trash(path: &Path) -> io::Result<PathBuf> {
+    use std::{fs, io::Write};
+
+    let path = std::path::absolute(path)?;
+    let trash = home_trash()?;
+    let files = trash.join("files");
+    let info = trash.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let name = path.file_name().ok_or(io::ErrorKind::InvalidInput)?;
+    for n in 0.. {
+        let mut entry = name.to_os_string();
+        if n > 0 {
+            entry.push(format!(".{n}"));
+        }
+        let mut info_name = entry.clone();
+        info_name.push(".trashinfo");
+
+        // creating the info file reserves the name
+        let mut file = match fs::File::create_new(info.join(&info_name)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+        let date = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S");
+        let written = writeln!(
+            file,
+            "[Trash Info]\nPath={}\nDeletionDate={date}",
+            encode(&path)
+        );
+
+        let dest = files.join(&entry);
+        if let Err(err) = written.and_then(|_| crate::watcher::move_path(&path, &dest)) {
+            let _ = fs::remove_file(info.join(&info_name));
+            return Err(err);
+        }
+        return Ok(dest);
+    }
+    unreachable!()
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub(crate) fn trash(_path: &Path) -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "trash is not supported on this platform",
+    ))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn home_trash() -> io::Result<PathBuf> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|h| h.join(".local/share")))
+        .ok_or(io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    Ok(data.join("Trash"))
+}
+
+/// Percent-encodes everything except unreserved characters and `/`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str()
+        .as_bytes()
+        .iter()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (*b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
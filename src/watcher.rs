@@ -1,64 +1,122 @@
 #![allow(clippy::unused_io_amount)]
 #![allow(unused_must_use)]
 
-use crossbeam_channel::{Sender, bounded};
+use crossbeam_channel::{Receiver, Sender, after, bounded, never, select};
 use notify::*;
 use notify_debouncer_full::new_debouncer;
 
 use std::fmt::Debug;
+use std::io;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{fs, path::PathBuf, thread, time::Duration};
 
+use crate::journal;
+use crate::settle::{Settling, Unsettled};
 use crate::*;
 
-const ICON_NOTHING: &str = "";
-const ICON_INFO: &str = "";
-const ICON_SUCCESS: &str = " "; // 
-const ICON_WARNING: &str = "";
-
-static EVENT_BUFFER: LazyLock<Mutex<Buffer<(String, EventKind)>>> =
-    LazyLock::new(|| Mutex::new(Buffer::with_capacity(9)));
-
 /// Internal
 pub(crate) enum QueueTask {
-    Move { src: PathBuf, dest: PathBuf },
+    Move {
+        src: PathBuf,
+        dest: PathBuf,
+        action: Action,
+        conflict: ConflictPolicy,
+        dedupe: Option<Dedupe>,
+        /// Asked on [`ConflictPolicy::Ask`]
+        module: Option<Arc<Mutex<dyn Module>>>,
+    },
+    /// Run an external program instead of touching the path.
+    Run {
+        src: PathBuf,
+        dest: PathBuf,
+        command: Arc<Command>,
+    },
+    /// Delete or trash, no destination involved.
+    Remove {
+        src: PathBuf,
+        trash: bool,
+    },
     Path(PathBuf),
     Info(String),
+    Warn(String),
     Ok(String),
     Err(String),
     None,
 }
 
-struct Schedule(QueueTask);
+/// Where a [`QueueTask`] came from, copied into its [`Outcome`].
+pub(crate) struct Origin {
+    pub(crate) label: Option<String>,
+    pub(crate) ruleset: PathBuf,
+    pub(crate) event: EventKind,
+}
+
+struct Schedule(QueueTask, Origin);
 
 pub enum Msg {
     None,
-    Text(String),
+    Outcome(Outcome),
 }
 
 impl QueueTask {
-    fn print_done(self) -> Msg {
-        let (code, icon, msg) = match self {
-            Self::Path(src) => (33, ICON_NOTHING, src.color_path()),
-            Self::Info(msg) => (37, ICON_INFO, msg),
-            Self::Ok(msg) => (32, ICON_SUCCESS, msg),
-            Self::Err(msg) => (31, ICON_WARNING, msg),
-            _ => return Msg::None,
-        };
-        Msg::Text(format!(" {} {msg}", color!(code, icon)))
+    /// Takes the source away, so no later task can act on it.
+    pub(crate) fn claims(&self) -> bool {
+        matches!(
+            self,
+            Self::Move {
+                action: Action::Move,
+                ..
+            } | Self::Remove { .. }
+        )
+    }
+
+    fn outcome(self) -> Option<Outcome> {
+        Some(match self {
+            Self::Path(src) => Outcome::new(OutcomeKind::Skipped).with_src(src),
+            Self::Info(msg) => Outcome::info(msg),
+            Self::Warn(msg) => Outcome::new(OutcomeKind::Warning).with_message(msg),
+            Self::Ok(msg) => Outcome::new(OutcomeKind::Success).with_message(msg),
+            Self::Err(msg) => Outcome::error(msg),
+            Self::Move {
+                src, dest, action, ..
+            } => Outcome::new(action_kind(action))
+                .with_src(src)
+                .with_dest(dest),
+            Self::Remove { src, trash } => Outcome::new(match trash {
+                true => OutcomeKind::Trashed,
+                false => OutcomeKind::Deleted,
+            })
+            .with_src(src),
+            Self::Run { src, dest, command } => Outcome::new(OutcomeKind::Success)
+                .with_message(command.line(&src, &dest))
+                .with_src(src)
+                .with_dest(dest),
+            Self::None => return None,
+        })
     }
 }
 
+impl From<Option<Outcome>> for Msg {
+    fn from(value: Option<Outcome>) -> Self {
+        value.map_or(Msg::None, Msg::Outcome)
+    }
+}
+
+/// Collects `--key=value` arguments. Flags without a value map to an empty string.
 fn parse_args() -> std::collections::HashMap<String, String> {
     std::env::args()
         .skip(1)
-        .filter_map(|s| s.split_once('=').map(|(a, b)| (a.to_owned(), b.to_owned())))
+        .map(|s| match s.split_once('=') {
+            Some((a, b)) => (a.to_owned(), b.to_owned()),
+            None => (s, String::new()),
+        })
         .collect()
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Config {
     /// Location for duplicate files for later inspection
     pub dump_folder: PathBuf,
@@ -67,29 +125,83 @@ pub struct Config {
     /// See [notify::Config]
     pub poll_interval: Option<Duration>,
     pub tick_rate: Option<Duration>,
+    /// Only report planned moves, nothing is touched on disk. Also enabled with `--dry-run`.
+    pub dry_run: bool,
+    /// Append every completed operation to this file, so it can be reverted with
+    /// [`crate::Journal::undo`]. Deletions are recorded too but can not be
+    /// reverted. Also set with `--journal=path`.
+    pub journal: Option<PathBuf>,
+}
+
+/// Stops a running [`Watch`]. Cheap to clone and safe to call more than once.
+#[derive(Clone)]
+pub struct WatchHandle {
+    stop_tx: Arc<Mutex<Option<Sender<()>>>>,
+}
+
+impl WatchHandle {
+    pub fn stop(&self) {
+        // dropping the only sender disconnects every receiver
+        self.stop_tx.lock().unwrap().take();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop_tx.lock().unwrap().is_none()
+    }
+}
+
+pub(crate) type ReloadFn<'a> =
+    dyn Fn(&Path) -> std::result::Result<Vec<Ruleset<'a>>, RulesError> + Send + Sync + 'a;
+
+/// Watcher thread of a single ruleset, stopped when dropped.
+struct Running<'a> {
+    rule: Arc<Ruleset<'a>>,
+    _stop_tx: Sender<()>,
 }
 
 pub struct Watch<'a> {
-    config: Config,
-    rules: Vec<Ruleset<'a>>,
+    pub(crate) config: Config,
+    pub(crate) rules: Vec<Arc<Ruleset<'a>>>,
 
-    filter: Option<String>,
+    pub(crate) filter: Option<String>,
+    handle: WatchHandle,
+    stop_rx: Receiver<()>,
+    /// Rules files watched for changes while running.
+    pub(crate) sources: Vec<PathBuf>,
+    pub(crate) reload: Option<Box<ReloadFn<'a>>>,
+    /// Misconfigured rulesets, reported by [`Watch::start`].
+    errors: Vec<crate::Error>,
 }
 
 impl<'a> Watch<'a> {
     pub fn new(mut config: Config) -> Self {
+        let mut args = parse_args();
+        if args.contains_key("--dry-run") {
+            config.dry_run = true;
+        }
+        if let Some(journal) = args.remove("--journal").filter(|j| !j.is_empty()) {
+            config.journal.replace(journal.into());
+        }
+
         let dump_folder = &config.dump_folder;
-        if dump_folder.try_exists().is_err() {
+        if !config.dry_run && dump_folder.try_exists().is_err() {
             std::fs::create_dir_all(dump_folder).expect("should create new dump folder");
         }
         config.poll_interval.get_or_insert(Duration::from_secs(2));
 
-        let mut args = parse_args();
+        let (stop_tx, stop_rx) = bounded(0);
 
         Self {
             config,
             rules: Vec::new(),
             filter: args.remove_entry("--filter").map(|(_, v)| v),
+            handle: WatchHandle {
+                stop_tx: Arc::new(Mutex::new(Some(stop_tx))),
+            },
+            stop_rx,
+            sources: Vec::new(),
+            reload: None,
+            errors: Vec::new(),
         }
     }
 
@@ -99,45 +211,96 @@ impl<'a> Watch<'a> {
         }
         let path: PathBuf = path.into();
         match path.exists() {
-            true => {
-                self.rules.push(Ruleset::new(path).unwrap());
-                f(self.rules.last_mut().unwrap());
-            }
+            true => match Ruleset::new(path) {
+                Ok(mut rule) => {
+                    f(&mut rule);
+                    self.rules.push(Arc::new(rule));
+                }
+                Err(error) => self.errors.push(error),
+            },
             false => println!("\x1b[31m# skipping {}\x1b[0m", path.display()),
         }
         self
     }
 
-    pub fn start(&mut self, send_print: impl Fn(Msg) + Send + Sync) -> notify::Result<()> {
+    pub(crate) fn push_rule(&mut self, rule: Ruleset<'a>) {
+        self.rules.push(Arc::new(rule));
+    }
+
+    /// Handle to stop [`Watch::start`] from another thread.
+    pub fn handle(&self) -> WatchHandle {
+        self.handle.clone()
+    }
+
+    /// Stop on SIGINT/SIGTERM (Ctrl-C on Windows). Can only be set once per process.
+    pub fn stop_on_signal(&self) -> std::result::Result<(), ctrlc::Error> {
+        let handle = self.handle();
+        ctrlc::set_handler(move || handle.stop())
+    }
+
+    /// First misconfiguration of added rulesets and their tasks.
+    pub fn validate(&self) -> crate::Result<()> {
+        let mut errors = self
+            .errors
+            .iter()
+            .chain(self.rules.iter().flat_map(|r| &r.errors));
+        match errors.next() {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Blocks until [`WatchHandle::stop`] is called. Watchers are stopped first,
+    /// then already scheduled tasks are finished before returning. May be
+    /// called again afterwards, with rules changed by reloads kept.
+    ///
+    /// Nothing is started when [`Watch::validate`] fails.
+    pub fn start(&mut self, send_print: impl Fn(Msg) + Send + Sync) -> crate::Result<()> {
+        self.validate()?;
+
         let (queue_tx, queue_rx) = bounded(0);
+        let running = Mutex::new(Vec::new());
+        let this = &*self;
+        let send_print = &send_print;
+        let running_ref = &running;
         thread::scope(|s| {
             let mut attached = Vec::new();
 
             // create watchers for each directory
-            for rule in &self.rules {
-                let flag = Arc::new(Mutex::new(AtomicBool::new(false)));
-                attached.push(flag.clone());
+            for rule in &this.rules {
+                let (watcher, flag, thread) = this.spawn_watcher(s, rule.clone(), queue_tx.clone());
+                running_ref.lock().unwrap().push(watcher);
+                attached.push((flag, thread));
+            }
+
+            if this.reload.is_some() && !this.sources.is_empty() {
+                let queue_tx = queue_tx.clone();
                 thread::Builder::new()
-                    .name(format!("watcher#{}", rule.watched_path.display()))
-                    .spawn_scoped(s, || {
-                        if let Err(error) = self.watch_one(&queue_tx, rule, flag) {
-                            use notify::ErrorKind as E;
-                            match error.kind {
-                                E::PathNotFound => {
-                                    eprintln!("Notfound {}", rule.watched_path.color_path())
-                                }
-                                _ => eprintln!("Error: {error:?}"),
-                            };
-                        };
+                    .name("rules".into())
+                    .spawn_scoped(s, move || {
+                        if let Err(error) = this.watch_rules(s, &queue_tx, running_ref, send_print)
+                        {
+                            eprintln!("Error: {error:?}");
+                        }
                     })
-                    .expect("building watcher");
+                    .expect("building rules watcher");
             }
+            // queue ends once every watcher has dropped its sender
+            drop(queue_tx);
 
             thread::Builder::new()
                 .name("queue_rx".into())
-                .spawn_scoped(s, || {
-                    for Schedule(queue_task) in queue_rx {
-                        send_print(self.handle_move_task(queue_task));
+                .spawn_scoped(s, move || {
+                    for Schedule(queue_task, origin) in queue_rx {
+                        let outcome =
+                            this.handle_move_task(queue_task, &origin)
+                                .map(|mut outcome| {
+                                    outcome.label = origin.label;
+                                    outcome.ruleset = Some(origin.ruleset);
+                                    outcome.event = Some(origin.event);
+                                    outcome
+                                });
+                        send_print(outcome.into());
                     }
                 })
                 .expect("building queue");
@@ -146,58 +309,486 @@ impl<'a> Watch<'a> {
             loop {
                 let count = attached
                     .iter()
-                    .filter(|x| x.lock().unwrap().load(Ordering::SeqCst))
+                    .filter(|(x, t)| x.lock().unwrap().load(Ordering::SeqCst) || t.is_finished())
                     .count();
-                if count == self.rules.len() {
+                if count == this.rules.len() || this.handle.is_stopped() {
                     break;
                 }
                 std::thread::sleep(Duration::from_millis(10));
             }
             println!("\x1b[37m# --------\x1b[0m");
+
+            // keep watchers alive until stopped, reloads may replace them meanwhile
+            _ = this.stop_rx.recv();
+            running_ref.lock().unwrap().clear();
         });
+        // pick up rules changed by reloads
+        self.rules = running
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.rule)
+            .collect();
+        // existing handles stop the next run
+        let (stop_tx, stop_rx) = bounded(0);
+        self.handle.stop_tx.lock().unwrap().replace(stop_tx);
+        self.stop_rx = stop_rx;
+        println!("\x1b[37m# stopped\x1b[0m");
         Ok(())
     }
 
-    fn handle_move_task(&self, task: QueueTask) -> Msg {
+    fn spawn_watcher<'scope>(
+        &'scope self,
+        s: &'scope thread::Scope<'scope, '_>,
+        rule: Arc<Ruleset<'a>>,
+        queue_tx: Sender<Schedule>,
+    ) -> (
+        Running<'a>,
+        Arc<Mutex<AtomicBool>>,
+        thread::ScopedJoinHandle<'scope, ()>,
+    ) {
+        let flag = Arc::new(Mutex::new(AtomicBool::new(false)));
+        let (rule_stop_tx, rule_stop_rx) = bounded(0);
+        let stop_rx = self.stop_rx.clone();
+        let inner_flag = flag.clone();
+        let inner_rule = rule.clone();
+        let thread = thread::Builder::new()
+            .name(format!("watcher#{}", rule.watched_path.display()))
+            .spawn_scoped(s, move || {
+                let rule = inner_rule;
+                let stop = [&stop_rx, &rule_stop_rx];
+                if let Err(error) = self.watch_one(&queue_tx, stop, &rule, inner_flag) {
+                    use notify::ErrorKind as E;
+                    match error.kind {
+                        E::PathNotFound => {
+                            eprintln!("Notfound {}", rule.watched_path.color_path())
+                        }
+                        _ => eprintln!("Error: {error:?}"),
+                    };
+                };
+            })
+            .expect("building watcher");
+        let running = Running {
+            rule,
+            _stop_tx: rule_stop_tx,
+        };
+        (running, flag, thread)
+    }
+
+    /// Watches rules files and applies changes to running watchers.
+    fn watch_rules<'scope>(
+        &'scope self,
+        s: &'scope thread::Scope<'scope, '_>,
+        scheduler: &'_ Sender<Schedule>,
+        running: &'_ Mutex<Vec<Running<'a>>>,
+        send_print: &'_ (impl Fn(Msg) + Send + Sync),
+    ) -> notify::Result<()> {
+        let (tx, rx) = bounded(0);
+        let mut debouncer = new_debouncer(
+            self.config.poll_interval.expect("poll_interval"),
+            self.config.tick_rate,
+            tx,
+        )?;
+        // editors often replace the file, so watch its parent instead
+        for source in &self.sources {
+            debouncer.watch(
+                source.parent().unwrap_or(source),
+                RecursiveMode::NonRecursive,
+            )?;
+        }
+
+        loop {
+            let events = select! {
+                recv(rx) -> result => match result {
+                    Ok(Ok(events)) => events,
+                    Ok(Err(errors)) => {
+                        errors.iter().for_each(|error| eprintln!("{error:?}"));
+                        continue;
+                    }
+                    Err(_) => break,
+                },
+                recv(self.stop_rx) -> _ => break,
+            };
+
+            let changed = self.sources.iter().filter(|source| {
+                events.iter().any(|event| {
+                    !event.kind.is_access() && event.paths.iter().any(|p| p == *source)
+                })
+            });
+            for source in changed {
+                for outcome in self.reload_rules(s, scheduler, running, source) {
+                    send_print(Msg::Outcome(outcome));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Diffs rulesets loaded from `source` against the running ones: new paths
+    /// get a watcher, removed paths are stopped, kept paths with changed settings
+    /// restarted and changed tasks of the others swapped.
+    fn reload_rules<'scope>(
+        &'scope self,
+        s: &'scope thread::Scope<'scope, '_>,
+        scheduler: &'_ Sender<Schedule>,
+        running: &'_ Mutex<Vec<Running<'a>>>,
+        source: &'_ Path,
+    ) -> Vec<Outcome> {
+        let reload = self.reload.as_ref().expect("reload");
+        let mut new = match reload(source) {
+            Ok(rules) => rules,
+            Err(err) => return vec![Outcome::error(format!("reload failed: {err}"))],
+        };
+
+        let mut msgs = Vec::new();
+        let mut restarted = Vec::new();
+        let mut running = running.lock().unwrap();
+        let mut kept = Vec::with_capacity(running.len());
+        for current in running.drain(..) {
+            if current.rule.source.as_deref() != Some(source) {
+                kept.push(current);
+                continue;
+            }
+            let path = &current.rule.watched_path;
+            let Some(idx) = new.iter().position(|n| n.watched_path == *path) else {
+                msgs.push(reloaded(path, format!("- {}", path.display())));
+                continue;
+            };
+            let rule = new.remove(idx);
+            if rule.same_settings(&current.rule) {
+                if rule.definition == current.rule.definition {
+                    // untouched, keep the running tasks and their modules
+                    kept.push(current);
+                    continue;
+                }
+                let tasks = rule.tasks.into_inner().unwrap();
+                msgs.push(reloaded(
+                    path,
+                    format!("~ {} ({} tasks)", path.display(), tasks.len()),
+                ));
+                *current.rule.tasks.write().unwrap() = tasks;
+                kept.push(current);
+            } else {
+                // watch mode, evaluation or history changed, start over
+                msgs.push(reloaded(path, format!("~ {} (restarted)", path.display())));
+                restarted.push(path.clone());
+                drop(current);
+                new.push(rule);
+            }
+        }
+
+        for rule in new {
+            if !restarted.contains(&rule.watched_path) {
+                let path = &rule.watched_path;
+                msgs.push(reloaded(path, format!("+ {}", path.display())));
+            }
+            let (watcher, ..) = self.spawn_watcher(s, Arc::new(rule), scheduler.clone());
+            kept.push(watcher);
+        }
+        *running = kept;
+        msgs
+    }
+
+    fn handle_move_task(&self, task: QueueTask, origin: &Origin) -> Option<Outcome> {
         match task {
-            QueueTask::Move { src, mut dest } => {
+            QueueTask::Move {
+                src,
+                mut dest,
+                action,
+                conflict,
+                dedupe,
+                module,
+            } => {
                 // TODO: what to do with this?
                 // if dest.to_string_lossy().len() > 259 {
                 //     panic!("{}", dest.print());
                 // }
 
+                if let Some(mode) = dedupe
+                    && src.is_file()
+                {
+                    match find_duplicate(&src, &dest) {
+                        // only a move may drop or divert the source
+                        Ok(Some(existing)) if action != Action::Move => {
+                            return Some(
+                                Outcome::new(OutcomeKind::Skipped)
+                                    .with_src(src)
+                                    .with_dest(existing)
+                                    .with_message("duplicate already present"),
+                            );
+                        }
+                        Ok(Some(existing)) => {
+                            return Some(self.handle_duplicate(mode, src, dest, existing, origin));
+                        }
+                        Ok(None) => {}
+                        Err(err) => return Some(Outcome::error(err.to_string()).with_src(src)),
+                    }
+                }
+
+                let mut kind = action_kind(action);
+                let mut notes = Vec::new();
+                let mut replace = false;
+                let mut timestamped = false;
+
                 if let Ok(true) = dest.try_exists() {
-                    std::mem::swap(&mut dest, &mut self.config.dump_folder.clone());
-                    dest.push(src.file_name().unwrap());
+                    let policy = match (conflict, module) {
+                        (ConflictPolicy::Ask, Some(module)) => {
+                            module.lock().unwrap().on_conflict(&src, &dest)
+                        }
+                        (policy, _) => policy,
+                    };
+                    let skipped = |reason: &str| {
+                        Some(
+                            Outcome::new(OutcomeKind::Skipped)
+                                .with_src(src.clone())
+                                .with_dest(dest.clone())
+                                .with_message(reason),
+                        )
+                    };
+
+                    let replaces = matches!(
+                        policy,
+                        ConflictPolicy::Overwrite
+                            | ConflictPolicy::KeepNewer
+                            | ConflictPolicy::KeepLarger
+                    );
+                    if replaces && let Some(reason) = kind_mismatch(&src, &dest) {
+                        return skipped(reason);
+                    }
+                    match policy {
+                        ConflictPolicy::Skip => return skipped("destination exists"),
+                        ConflictPolicy::Overwrite => replace = true,
+                        ConflictPolicy::KeepNewer => match is_newer(&src, &dest) {
+                            true => replace = true,
+                            false => return skipped("destination is newer"),
+                        },
+                        ConflictPolicy::KeepLarger => match is_larger(&src, &dest) {
+                            true => replace = true,
+                            false => return skipped("destination is larger"),
+                        },
+                        ConflictPolicy::KeepBoth => {
+                            dest = numbered(&dest);
+                            notes.push("kept both");
+                        }
+                        ConflictPolicy::DumpFolder | ConflictPolicy::Ask => {
+                            dest = self.config.dump_folder.clone();
+                            dest.push(src.file_name().unwrap());
+                            kind = OutcomeKind::Diverted;
+                            notes.push("diverted to dump folder");
+
+                            if dest.exists() {
+                                // add timestamp if more duplicates are possible
+                                let mut name = dest.file_name().unwrap_or_default().to_os_string();
+                                name.push(".");
+                                name.push(crate::timestamp());
+                                dest.set_file_name(name);
+                                notes.push("timestamp added");
+                                timestamped = true;
+                            }
+                        }
+                    }
+                    if replace {
+                        notes.push("overwritten");
+                    }
+                }
+
+                let mut outcome = Outcome::new(kind).with_src(src.clone());
+                if !notes.is_empty() {
+                    outcome.message.replace(notes.join(", "));
                 }
 
-                if dest.exists() {
-                    // add timestamp if more duplicates are possible
-                    let ext = dest.extension().unwrap().to_str().unwrap().to_string();
-                    dest.set_extension(ext + "." + &crate::timestamp());
+                if self.config.dry_run {
+                    outcome.dry_run = true;
+                    return Some(outcome.with_dest(dest));
                 }
 
                 if dest.file_stem().is_some() {
                     let mut temp = dest.clone();
                     temp.pop();
-                    fs::create_dir(temp);
+                    fs::create_dir_all(temp);
                 }
 
-                match fs::rename(&src, &dest) {
-                    Ok(_) => QueueTask::Ok(dest.color_path()),
-                    Err(err) => {
-                        QueueTask::Err(format!("{}  {}", src.color_path(), color!(31, err)))
+                // files are replaced by rename, directories and links are not
+                let cleared = match replace {
+                    true if dest.is_dir() && !dest.is_symlink() => fs::remove_dir_all(&dest),
+                    true if matches!(action, Action::Hardlink | Action::Symlink) => {
+                        fs::remove_file(&dest)
                     }
+                    _ => Ok(()),
+                };
+
+                let done = cleared.and_then(|_| match action {
+                    Action::Copy => copy_into_place(&src, &dest),
+                    Action::Hardlink => fs::hard_link(&src, &dest),
+                    Action::Symlink => symlink(&src, &dest),
+                    _ => move_path(&src, &dest),
+                });
+                if let Err(err) = done {
+                    return Some(Outcome::error(err.to_string()).with_src(src));
+                }
+                let op = match action {
+                    Action::Copy => journal::Op::Copy,
+                    Action::Hardlink => journal::Op::Hardlink,
+                    Action::Symlink => journal::Op::Symlink,
+                    _ => journal::Op::Move,
+                };
+                self.record(&mut outcome, || {
+                    let mut entry = journal::Entry::new(op, &src, &dest, origin)?;
+                    entry.diverted = kind == OutcomeKind::Diverted;
+                    entry.timestamped = timestamped;
+                    entry.replaced = replace;
+                    Ok(entry)
+                });
+                Some(outcome.with_dest(dest))
+            }
+            QueueTask::Remove { src, trash } => {
+                let mut outcome = QueueTask::Remove {
+                    src: src.clone(),
+                    trash,
+                }
+                .outcome()?;
+                if self.config.dry_run {
+                    outcome.dry_run = true;
+                    return Some(outcome);
+                }
+
+                let done = match trash {
+                    true => crate::trash::trash(&src).map(|path| {
+                        self.record(&mut outcome, || {
+                            journal::Entry::new(journal::Op::Trash, &src, &path, origin)
+                        });
+                        outcome.dest.replace(path);
+                    }),
+                    false if src.is_dir() && !src.is_symlink() => fs::remove_dir_all(&src),
+                    false => fs::remove_file(&src),
+                };
+                if !trash && done.is_ok() {
+                    self.record(&mut outcome, || {
+                        journal::Entry::new(journal::Op::Delete, &src, &src, origin)
+                    });
+                }
+                Some(match done {
+                    Ok(_) => outcome,
+                    Err(err) => Outcome::error(err.to_string()).with_src(src),
+                })
+            }
+            QueueTask::Run { src, dest, command } => Some(match self.config.dry_run {
+                true => {
+                    let mut outcome = QueueTask::Run { src, dest, command }.outcome()?;
+                    outcome.dry_run = true;
+                    outcome
+                }
+                false => command.run(&src, &dest, origin),
+            }),
+            rest => rest.outcome(),
+        }
+    }
+
+    fn handle_duplicate(
+        &self,
+        mode: Dedupe,
+        src: PathBuf,
+        dest: PathBuf,
+        existing: PathBuf,
+        origin: &Origin,
+    ) -> Outcome {
+        let mut outcome = Outcome::new(OutcomeKind::Duplicate).with_src(src.clone());
+        outcome.dry_run = self.config.dry_run;
+
+        let dropped = |outcome: &mut Outcome| {
+            fs::remove_file(&src).map(|_| {
+                self.record(outcome, || {
+                    journal::Entry::new(journal::Op::DedupeDrop, &src, &existing, origin)
+                })
+            })
+        };
+        let result = match mode {
+            Dedupe::Drop => {
+                outcome.message.replace("dropped".into());
+                outcome.dest.replace(existing.clone());
+                match self.config.dry_run {
+                    true => Ok(()),
+                    false => dropped(&mut outcome),
+                }
+            }
+            Dedupe::DumpFolder => {
+                let mut dump = self.config.dump_folder.join(src.file_name().unwrap());
+                if dump.exists() {
+                    dump = numbered(&dump);
+                }
+                outcome.kind = OutcomeKind::Diverted;
+                outcome
+                    .message
+                    .replace(format!("duplicate of {}", existing.display()));
+                outcome.dest.replace(dump.clone());
+                match self.config.dry_run {
+                    true => Ok(()),
+                    false => move_path(&src, &dump).map(|_| {
+                        self.record(&mut outcome, || {
+                            let mut entry =
+                                journal::Entry::new(journal::Op::Move, &src, &dump, origin)?;
+                            entry.diverted = true;
+                            Ok(entry)
+                        })
+                    }),
+                }
+            }
+            Dedupe::Hardlink if dest == existing => {
+                // same name, the content is already there
+                outcome.message.replace("dropped".into());
+                outcome.dest.replace(existing.clone());
+                match self.config.dry_run {
+                    true => Ok(()),
+                    false => dropped(&mut outcome),
+                }
+            }
+            Dedupe::Hardlink => {
+                let link = match dest.exists() {
+                    true => numbered(&dest),
+                    false => dest,
+                };
+                outcome
+                    .message
+                    .replace(format!("hardlinked to {}", existing.display()));
+                outcome.dest.replace(link.clone());
+                match self.config.dry_run {
+                    true => Ok(()),
+                    false => fs::hard_link(&existing, &link)
+                        .and_then(|_| fs::remove_file(&src))
+                        .map(|_| {
+                            self.record(&mut outcome, || {
+                                journal::Entry::new(journal::Op::DedupeLink, &src, &link, origin)
+                            })
+                        }),
                 }
             }
-            rest => rest,
+        };
+
+        match result {
+            Ok(_) => outcome,
+            Err(err) => Outcome::error(err.to_string()).with_src(src),
+        }
+    }
+
+    /// Appends to [`Config::journal`] when set, failures are noted in the outcome.
+    fn record(&self, outcome: &mut Outcome, entry: impl FnOnce() -> io::Result<journal::Entry>) {
+        let Some(path) = &self.config.journal else {
+            return;
+        };
+        if let Err(err) = entry().and_then(|entry| journal::append(path, entry)) {
+            let note = format!("not journaled: {err}");
+            outcome.message = Some(match outcome.message.take() {
+                Some(message) => format!("{message}, {note}"),
+                None => note,
+            });
         }
-        .print_done()
     }
 
     fn watch_one(
         &self,
         scheduler: &'_ Sender<Schedule>,
+        stop: [&'_ Receiver<()>; 2],
         rule: &'_ Ruleset<'_>,
         flag: Arc<Mutex<AtomicBool>>,
     ) -> notify::Result<()> {
@@ -228,81 +819,532 @@ impl<'a> Watch<'a> {
             .unwrap()
             .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |_| Some(true));
 
-        'recv: for result in rx {
+        let mut settling = Settling::default();
+        if rule.scan_existing {
+            Self::backfill(scheduler, rule, &self.config, &mut settling);
+        }
+
+        'recv: loop {
+            let tick = settling.next_check().map_or_else(never, after);
+            let result = select! {
+                recv(rx) -> result => match result {
+                    Ok(result) => result,
+                    Err(_) => break 'recv,
+                },
+                // only ever disconnect
+                recv(stop[0]) -> _ => break 'recv,
+                recv(stop[1]) -> _ => break 'recv,
+                recv(tick) -> _ => {
+                    for (path, unsettled) in settling.settled() {
+                        Self::resume(scheduler, rule, &path, unsettled, &self.config, &mut settling);
+                    }
+                    continue 'recv;
+                }
+            };
             match result {
-                Ok(events) => {
-                    let Some(buf) = &mut EVENT_BUFFER.lock().ok() else {
-                        continue 'recv;
+                Ok(events) => events.iter().for_each(|event| {
+                    let path = event.paths.last().expect("last event path");
+                    let history = rule.history.lock().unwrap().get(path);
+                    let trigger = Trigger {
+                        kind: event.kind,
+                        path,
+                        from: event
+                            .paths
+                            .first()
+                            .filter(|_| event.paths.len() > 1)
+                            .map(|p| p.as_path()),
+                        root: &rule.watched_path,
+                        history: &history,
                     };
+                    Self::dispatch(scheduler, rule, &trigger, &self.config, &mut settling, None);
+                    rule.history.lock().unwrap().push(path, event.kind);
+                }),
+                Err(errors) => errors.iter().for_each(|error| eprintln!("{error:?}")),
+            }
+        }
 
-                    events.iter().for_each(|event| {
-                        let path = event.paths.last().expect("last event path");
-                        let file_stem = path.file_stem().unwrap().to_string_lossy().to_string();
-                        let prev = buf.get_with_key(&file_stem);
+        Ok(())
+    }
 
-                        for inner in &rule.tasks {
-                            let task = inner.task.lock().unwrap();
+    /// Runs every task of the rule against a single event. A task waiting for
+    /// the path to settle parks the event in `settling` along with the tasks
+    /// after it, see [`Self::resume`].
+    fn dispatch<'t>(
+        scheduler: &'_ Sender<Schedule>,
+        rule: &'_ Ruleset<'t>,
+        trigger: &Trigger,
+        config: &Config,
+        settling: &mut Settling<'t>,
+        resumed: Option<Unsettled<'t>>,
+    ) {
+        let path = trigger.path;
+        let tasks = rule.tasks.read().unwrap();
+        let order = rule.order(&tasks);
+        // label of the task which moved or removed the path
+        let (mut claimed, start, settled) = match resumed {
+            None => (None, 0, Duration::ZERO),
+            Some(unsettled) => {
+                // gone when the tasks were reloaded during the wait
+                let Some(start) = order
+                    .iter()
+                    .position(|i| Arc::ptr_eq(&tasks[*i].task, &unsettled.task))
+                else {
+                    return;
+                };
+                let settled = unsettled.settled_for();
+                (unsettled.claimed, start, settled)
+            }
+        };
+        for inner in order[start..].iter().map(|i| &tasks[*i]) {
+            let task = inner.task.lock().unwrap();
 
-                            match task.watched_types {
-                                WatchingKind::Dirs if !path.is_dir() => continue,
-                                WatchingKind::Files if !path.is_file() => continue,
-                                _ => {}
-                            }
+            match task.watched_types {
+                WatchingKind::Dirs if !path.is_dir() => continue,
+                WatchingKind::Files if !path.is_file() => continue,
+                _ => {}
+            }
 
-                            match task.event_check {
-                                None => continue,
-                                Some(f) => {
-                                    if !f(event.kind, prev) {
-                                        continue;
-                                    }
-                                }
-                            };
-
-                            let queue_task = task.parse(path.to_owned(), inner.dest.to_owned());
-                            scheduler.send(Schedule(queue_task));
-                        }
+            if !task
+                .event_check
+                .as_ref()
+                .is_some_and(|f| f.matches(trigger))
+            {
+                continue;
+            }
 
-                        buf.push((file_stem, event.kind));
-                    });
+            if let Some(period) = task.stable_period
+                && period > settled
+                && !task.is_partial(path)
+            {
+                settling.wait(trigger, Arc::clone(&inner.task), claimed, period);
+                return;
+            }
+
+            let mut queue_task = task.parse(trigger, inner.dest.to_owned(), config);
+            if matches!(queue_task, QueueTask::None) {
+                continue;
+            }
+            // nothing may act on a path moved or removed by an earlier task
+            match &claimed {
+                Some(by) => {
+                    queue_task = QueueTask::Warn(format!(
+                        "{} already taken by task {}, skipped",
+                        path.display(),
+                        by.as_deref().unwrap_or("unnamed")
+                    ))
                 }
-                Err(errors) => errors.iter().for_each(|error| eprintln!("{error:?}")),
+                None if queue_task.claims() => claimed = Some(task.label.clone()),
+                None => {}
+            }
+            let origin = Origin {
+                label: task.label.clone(),
+                ruleset: rule.watched_path.clone(),
+                event: trigger.kind,
+            };
+            scheduler.send(Schedule(queue_task, origin));
+            if rule.evaluation != Evaluation::All {
+                break;
             }
         }
+    }
 
-        Ok(())
+    /// Dispatches a parked event again, starting at the task which waited.
+    fn resume<'t>(
+        scheduler: &'_ Sender<Schedule>,
+        rule: &'_ Ruleset<'t>,
+        path: &Path,
+        unsettled: Unsettled<'t>,
+        config: &Config,
+        settling: &mut Settling<'t>,
+    ) {
+        let history = unsettled.history.clone();
+        let from = unsettled.from.clone();
+        let trigger = Trigger {
+            kind: unsettled.kind,
+            path,
+            from: from.as_deref(),
+            root: &rule.watched_path,
+            history: &history,
+        };
+        Self::dispatch(scheduler, rule, &trigger, config, settling, Some(unsettled));
+    }
+
+    /// Feeds entries already present in the watched directory as create events.
+    /// Entries waiting to settle do not hold up the ones after them.
+    fn backfill<'t>(
+        scheduler: &'_ Sender<Schedule>,
+        rule: &'_ Ruleset<'t>,
+        config: &Config,
+        settling: &mut Settling<'t>,
+    ) {
+        use notify::event::CreateKind;
+
+        let mut entries = Vec::new();
+        let mut pending = vec![rule.watched_path.clone()];
+        while let Some(dir) = pending.pop() {
+            let Ok(read_dir) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in read_dir.flatten() {
+                let path = entry.path();
+                if rule.recursive_mode == RecursiveMode::Recursive && path.is_dir() {
+                    pending.push(path.clone());
+                }
+                entries.push(path);
+            }
+        }
+        entries.sort();
+
+        for path in entries {
+            let kind = match path.is_dir() {
+                true => EventKind::Create(CreateKind::Folder),
+                false => EventKind::Create(CreateKind::File),
+            };
+            let trigger = Trigger {
+                kind,
+                path: &path,
+                from: None,
+                root: &rule.watched_path,
+                history: &History::default(),
+            };
+            Self::dispatch(scheduler, rule, &trigger, config, settling, None);
+        }
     }
 }
 
-struct Buffer<T>(Vec<T>);
+fn reloaded(path: &Path, message: String) -> Outcome {
+    let mut outcome = Outcome::info(message);
+    outcome.ruleset = Some(path.to_path_buf());
+    outcome
+}
 
-impl<T> Default for Buffer<T> {
-    fn default() -> Self {
-        Self::with_capacity(9)
+/// Only a file replaces a file and a directory a directory, anything else would
+/// wipe a tree the journal can not restore. Links count as files.
+pub(crate) fn kind_mismatch(src: &Path, dest: &Path) -> Option<&'static str> {
+    let is_dir = |p: &Path| fs::symlink_metadata(p).is_ok_and(|m| m.is_dir());
+    match (is_dir(src), is_dir(dest)) {
+        (false, true) => Some("destination is a directory"),
+        (true, false) => Some("destination is not a directory"),
+        _ => None,
     }
 }
 
-impl<T> Buffer<T> {
-    fn with_capacity(capacity: usize) -> Self {
-        Self(Vec::with_capacity(capacity))
+pub(crate) fn is_newer(src: &Path, dest: &Path) -> bool {
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    modified(src) > modified(dest)
+}
+
+pub(crate) fn is_larger(src: &Path, dest: &Path) -> bool {
+    let len = |p: &Path| fs::metadata(p).map(|m| m.len()).ok();
+    len(src) > len(dest)
+}
+
+/// File in the directory of `dest` with the same content as `src`, preferring
+/// `dest` itself. Sizes are compared first, content only for same sized files.
+pub(crate) fn find_duplicate(src: &Path, dest: &Path) -> io::Result<Option<PathBuf>> {
+    let len = fs::metadata(src)?.len();
+    let Some(dir) = dest.parent().filter(|d| d.is_dir()) else {
+        return Ok(None);
+    };
+
+    let mut candidates: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p != src && fs::metadata(p).is_ok_and(|m| m.is_file() && m.len() == len))
+        .collect();
+    candidates.sort_by_key(|p| p != dest);
+
+    for candidate in candidates {
+        if same_content(src, &candidate).unwrap_or(false) {
+            return Ok(Some(candidate));
+        }
     }
+    Ok(None)
+}
+
+/// Compares byte by byte, a duplicate may get its source removed.
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::io::Read;
 
-    fn push(&mut self, item: T) {
-        if self.0.len() == self.0.capacity() {
-            self.0.remove(0);
+    let (mut a, mut b) = (fs::File::open(a)?, fs::File::open(b)?);
+    let (mut buf_a, mut buf_b) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            // equal length was checked, but the file may have grown since
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        if b.read_exact(&mut buf_b[..n]).is_err() || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
         }
-        self.0.push(item);
     }
+}
 
-    fn remove(&mut self, idx: usize) -> T {
-        self.0.remove(idx)
+/// First free `name (n).ext` beside `dest`.
+pub(crate) fn numbered(dest: &Path) -> PathBuf {
+    let stem = dest.file_stem().unwrap_or_default();
+    let ext = dest.extension();
+    (1..)
+        .map(|n| {
+            let mut name = stem.to_os_string();
+            name.push(format!(" ({n})"));
+            if let Some(ext) = ext {
+                name.push(".");
+                name.push(ext);
+            }
+            dest.with_file_name(name)
+        })
+        .find(|p| !p.exists())
+        .expect("free file name")
+}
+
+/// Renames `src` to `dest`, falling back to copy and remove when both paths
+/// are on different filesystems.
+pub(crate) fn move_path(src: &Path, dest: &Path) -> io::Result<()> {
+    match fs::rename(src, dest) {
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => move_across(src, dest),
+        rest => rest,
     }
 }
 
-impl<K: std::cmp::PartialEq, V> Buffer<(K, V)> {
-    fn get_with_key(&mut self, needle: &K) -> Option<V> {
-        self.0
-            .iter()
-            .position(|(k, _)| *k == *needle)
-            .map(|idx| self.remove(idx).1)
+/// Copies `src` to `dest`, then removes the source.
+fn move_across(src: &Path, dest: &Path) -> io::Result<()> {
+    copy_into_place(src, dest)?;
+    if src.is_dir() {
+        fs::remove_dir_all(src)
+    } else {
+        fs::remove_file(src)
+    }
+}
+
+/// Copies `src` into a temporary path beside `dest`, then renames it into place
+/// so that `dest` never holds partial content.
+fn copy_into_place(src: &Path, dest: &Path) -> io::Result<()> {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(dest.file_name().unwrap_or_default());
+    name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        crate::timestamp()
+    ));
+    let temp = dest.with_file_name(name);
+
+    let copied = copy_synced(src, &temp).and_then(|_| fs::rename(&temp, dest));
+    if let Err(err) = copied {
+        if temp.is_dir() {
+            fs::remove_dir_all(&temp);
+        } else {
+            fs::remove_file(&temp);
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Links `dest` to the absolute path of `src`.
+fn symlink(src: &Path, dest: &Path) -> io::Result<()> {
+    let target = std::path::absolute(src)?;
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(target, dest);
+    #[cfg(windows)]
+    return match target.is_dir() {
+        true => std::os::windows::fs::symlink_dir(target, dest),
+        false => std::os::windows::fs::symlink_file(target, dest),
+    };
+}
+
+fn action_kind(action: Action) -> OutcomeKind {
+    match action {
+        Action::Copy => OutcomeKind::Copied,
+        Action::Hardlink | Action::Symlink => OutcomeKind::Linked,
+        Action::Delete => OutcomeKind::Deleted,
+        Action::Trash => OutcomeKind::Trashed,
+        _ => OutcomeKind::Moved,
+    }
+}
+
+/// Copies file or directory tree, flushing content to disk and preserving
+/// permissions and modification time.
+fn copy_synced(src: &Path, dest: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(src)?;
+    if meta.is_dir() {
+        fs::create_dir(dest)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_synced(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else if meta.is_symlink() {
+        let target = fs::read_link(src)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(target, dest)?;
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_file(target, dest)?;
+        return Ok(());
+    } else {
+        let mut reader = fs::File::open(src)?;
+        let mut writer = fs::File::create(dest)?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.sync_all()?;
+    }
+
+    let file = open_for_times(dest, meta.is_dir())?;
+    file.set_modified(meta.modified()?)?;
+    // flushing a directory handle is refused on windows
+    if cfg!(unix) || meta.is_file() {
+        file.sync_all()?;
+    }
+    fs::set_permissions(dest, meta.permissions())
+}
+
+/// Opens `path` so its times can be set. Windows needs write access and, for
+/// directories, backup semantics.
+fn open_for_times(path: &Path, dir: bool) -> io::Result<fs::File> {
+    let mut options = fs::File::options();
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        options.write(true);
+        if dir {
+            options.custom_flags(FILE_FLAG_BACKUP_SEMANTICS);
+        }
+    }
+    #[cfg(not(windows))]
+    options.write(!dir).read(dir);
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dedupes {
+        watch: Watch<'static>,
+        src: PathBuf,
+        dest: PathBuf,
+        existing: PathBuf,
+    }
+
+    /// `in/a.txt` moved into `out`, which holds `b.txt` with `existing` as content.
+    fn setup(name: &str, existing: &str) -> Dedupes {
+        let dir = crate::scratch(name);
+        for sub in ["in", "out", "dump"] {
+            fs::create_dir(dir.join(sub)).unwrap();
+        }
+        let src = dir.join("in/a.txt");
+        fs::write(&src, "same content").unwrap();
+        fs::write(dir.join("out/b.txt"), existing).unwrap();
+        let watch = Watch::new(Config {
+            dump_folder: dir.join("dump"),
+            journal: Some(dir.join("journal.toml")),
+            ..Default::default()
+        });
+        Dedupes {
+            watch,
+            src,
+            dest: dir.join("out/a.txt"),
+            existing: dir.join("out/b.txt"),
+        }
+    }
+
+    fn run(d: &Dedupes, mode: Dedupe) -> Outcome {
+        let task = QueueTask::Move {
+            src: d.src.clone(),
+            dest: d.dest.clone(),
+            action: Action::Move,
+            conflict: ConflictPolicy::default(),
+            dedupe: Some(mode),
+            module: None,
+        };
+        let origin = Origin {
+            label: None,
+            ruleset: d.src.parent().unwrap().to_path_buf(),
+            event: EventKind::Any,
+        };
+        d.watch.handle_move_task(task, &origin).unwrap()
+    }
+
+    #[test]
+    fn dedupe_drop_removes_source() {
+        let d = setup("dedupe-drop", "same content");
+        let outcome = run(&d, Dedupe::Drop);
+        assert_eq!(outcome.kind, OutcomeKind::Duplicate);
+        assert_eq!(outcome.dest.as_deref(), Some(d.existing.as_path()));
+        assert!(!d.src.exists());
+        assert!(!d.dest.exists());
+
+        let journal = Journal::new(d.watch.config.journal.as_ref().unwrap());
+        let outcomes = journal.undo(Undo::Last(1)).unwrap();
+        assert_eq!(outcomes[0].kind, OutcomeKind::Reverted);
+        assert_eq!(fs::read_to_string(&d.src).unwrap(), "same content");
+        assert!(d.existing.exists());
+    }
+
+    #[test]
+    fn dedupe_dump_folder_diverts_source() {
+        let d = setup("dedupe-dump", "same content");
+        let outcome = run(&d, Dedupe::DumpFolder);
+        let dumped = d.watch.config.dump_folder.join("a.txt");
+        assert_eq!(outcome.kind, OutcomeKind::Diverted);
+        assert_eq!(outcome.dest.as_deref(), Some(dumped.as_path()));
+        assert!(!d.src.exists());
+        assert_eq!(fs::read_to_string(dumped).unwrap(), "same content");
+    }
+
+    #[test]
+    fn dedupe_hardlink_links_existing() {
+        let d = setup("dedupe-hardlink", "same content");
+        run(&d, Dedupe::Hardlink);
+        assert!(!d.src.exists());
+        assert_eq!(fs::read_to_string(&d.dest).unwrap(), "same content");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let ino = |p: &Path| fs::metadata(p).unwrap().ino();
+            assert_eq!(ino(&d.dest), ino(&d.existing));
+        }
+
+        let journal = Journal::new(d.watch.config.journal.as_ref().unwrap());
+        let outcomes = journal.undo(Undo::Last(1)).unwrap();
+        assert_eq!(outcomes[0].kind, OutcomeKind::Reverted);
+        assert_eq!(fs::read_to_string(&d.src).unwrap(), "same content");
+        assert!(!d.dest.exists());
+        assert!(d.existing.exists());
+    }
+
+    #[test]
+    fn overwrite_keeps_directory_in_the_way_of_a_file() {
+        let dir = crate::scratch("overwrite-kind");
+        let (src, dest) = (dir.join("photos"), dir.join("out/photos"));
+        fs::write(&src, "file").unwrap();
+        fs::create_dir_all(dest.join("2024")).unwrap();
+        let watch = Watch::new(Config::default());
+        let task = QueueTask::Move {
+            src: src.clone(),
+            dest: dest.clone(),
+            action: Action::Move,
+            conflict: ConflictPolicy::Overwrite,
+            dedupe: None,
+            module: None,
+        };
+        let origin = Origin {
+            label: None,
+            ruleset: dir.clone(),
+            event: EventKind::Any,
+        };
+        let outcome = watch.handle_move_task(task, &origin).unwrap();
+        assert_eq!(outcome.kind, OutcomeKind::Skipped);
+        assert!(src.is_file());
+        assert!(dest.join("2024").is_dir());
+    }
+
+    #[test]
+    fn dedupe_keeps_same_size_different_content() {
+        let d = setup("dedupe-differs", "same_content");
+        let outcome = run(&d, Dedupe::Drop);
+        assert_eq!(outcome.kind, OutcomeKind::Moved);
+        assert!(!d.src.exists());
+        assert_eq!(fs::read_to_string(&d.dest).unwrap(), "same content");
+        assert_eq!(fs::read_to_string(&d.existing).unwrap(), "same_content");
     }
 }